@@ -0,0 +1,488 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Command line layer: the options override the default config
+//
+////////////////////////////////////////////////////////////////////////////////
+
+extern crate clap;
+
+use crate::types::*;
+use clap::{App, Arg, ArgMatches};
+use rand::prelude::*;
+use std::ffi::OsString;
+use std::fs;
+
+// from the command line options of the process. An invalid option, or an
+//   invalid config, is returned as an error message.
+pub fn get_config() -> Result<Config, String> {
+    get_config_from(std::env::args_os())
+}
+
+// from the given arguments, the first one being the name of the binary
+pub fn get_config_from<I, T>(args: I) -> Result<Config, String>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = get_app().get_matches_from(args);
+    let mut config = Config::default();
+
+    // the options given on the command line override the scenario
+    let mut scenario = match matches.value_of("scenario") {
+        Some(path) => Scenario::from_file(path)?,
+        None => Scenario::default(),
+    };
+
+    // except for the preset, which goes under the spec overrides of the
+    //   scenario, as well as under the ones of the command line
+    if let Some(preset) = matches.value_of("spec") {
+        scenario.spec.preset = Some(preset.to_string());
+    }
+    scenario.apply(&mut config)?;
+    apply_matches(&mut config, &matches)?;
+
+    // a run can be reproduced with the seed printed here
+    if scenario.seed.is_none() && matches.value_of("seed").is_none() {
+        config.seed = thread_rng().gen();
+        eprintln!("seed: {}", config.seed);
+    }
+
+    config.build()
+}
+
+pub fn get_app() -> App<'static, 'static> {
+    App::new("Eth2 Reward Simulator")
+        .arg(
+            Arg::with_name("scenario")
+                .long("scenario")
+                .value_name("FILE")
+                .help("Scenario file (.toml or .json), overridden by the other options"),
+        )
+        .arg(
+            Arg::with_name("initial_stake")
+                .short("i")
+                .long("initial_stake")
+                .value_name("ETH")
+                .help("Your initial stake in ETH"),
+        )
+        .arg(
+            Arg::with_name("queued_stake")
+                .short("q")
+                .long("queued_stake")
+                .value_name("ETH")
+                .help("Stake in ETH waiting in the activation queue at the start"),
+        )
+        .arg(
+            Arg::with_name("deposits_per_epoch")
+                .long("deposits_per_epoch")
+                .value_name("n")
+                .help("New validators depositing at each epoch"),
+        )
+        .arg(
+            Arg::with_name("deposits_file")
+                .long("deposits_file")
+                .value_name("CSV")
+                .help("Deposits as lines of epoch,ETH or epoch,ETH,validator_index (top-up)"),
+        )
+        .arg(
+            Arg::with_name("epochs")
+                .short("e")
+                .long("epochs")
+                .value_name("t")
+                .help("Epochs to run"),
+        )
+        .arg(
+            Arg::with_name("runs")
+                .short("n")
+                .long("runs")
+                .value_name("n")
+                .help("Independent runs, aggregated in a Monte Carlo report if more than 1"),
+        )
+        .arg(
+            Arg::with_name("probability_online")
+                .short("p")
+                .long("probability_online")
+                .value_name("p")
+                .help("A value in [0,1]"),
+        )
+        .arg(
+            Arg::with_name("probability_honest")
+                .long("probability_honest")
+                .value_name("p")
+                .help("Probability of a validator behaving honestly at each epoch, in [0,1]"),
+        )
+        .arg(
+            Arg::with_name("dishonest_behaviour")
+                .long("dishonest_behaviour")
+                .value_name("behaviour")
+                .help(
+                    "What dishonest validators do (wrong_source, wrong_target, wrong_head, equivocation)",
+                ),
+        )
+        .arg(
+            Arg::with_name("probability_target")
+                .long("probability_target")
+                .value_name("p")
+                .help("Probability of a correct target vote, given a correct source, in [0,1]"),
+        )
+        .arg(
+            Arg::with_name("probability_head")
+                .long("probability_head")
+                .value_name("p")
+                .help("Probability of a correct head vote, given a correct target, in [0,1]"),
+        )
+        .arg(
+            Arg::with_name("printing_output")
+                .short("r")
+                .long("printing_output")
+                .value_name("option")
+                .help("Type of report (epoch, monthly)"),
+        )
+        .arg(
+            Arg::with_name("probability_slashed")
+                .short("s")
+                .long("probability_slashed")
+                .value_name("p")
+                .help("Probability of a validator being slashed at each epoch, in [0,1]"),
+        )
+        .arg(
+            Arg::with_name("slashings")
+                .long("slashings")
+                .value_name("epoch:count,...")
+                .help("Number of validators to slash at the given epochs"),
+        )
+        .arg(
+            Arg::with_name("exit_policy")
+                .short("x")
+                .long("exit_policy")
+                .value_name("policy")
+                .help("Voluntary exits (none, rate:<n>, apr:<rate>:<n>, batch:<epoch>:<n>)"),
+        )
+        .arg(
+            Arg::with_name("inclusion_delay")
+                .long("inclusion_delay")
+                .value_name("model")
+                .help("Attestation inclusion delay (expected, sampled, empirical:<w1>,<w2>,...)"),
+        )
+        .arg(
+            Arg::with_name("execution_rewards")
+                .long("execution_rewards")
+                .value_name("model")
+                .help("Priority fees and MEV of each block, to the fee recipient (none, fixed:<Gwei>, lognormal:<median Gwei>:<sigma>)"),
+        )
+        .arg(
+            Arg::with_name("execution_rewards_file")
+                .long("execution_rewards_file")
+                .value_name("CSV")
+                .help("Execution rewards of historical blocks in ETH, one per line, replayed in order"),
+        )
+        .arg(
+            Arg::with_name("fork")
+                .long("fork")
+                .value_name("fork")
+                .help("Rewards and penalties of the fork (phase0, altair)"),
+        )
+        .arg(
+            Arg::with_name("engine")
+                .long("engine")
+                .value_name("engine")
+                .help("Simulation engine (validators, cohorts, analytic)"),
+        )
+        .arg(
+            Arg::with_name("cohort_bucket")
+                .long("cohort_bucket")
+                .value_name("Gwei")
+                .help("Width of the balance buckets of the cohorts engine"),
+        )
+        .arg(
+            Arg::with_name("spec")
+                .long("spec")
+                .value_name("preset")
+                .help("Preset of the spec constants (mainnet, minimal)"),
+        )
+        .arg(
+            Arg::with_name("spec_overrides")
+                .long("spec_overrides")
+                .value_name("NAME=value,...")
+                .help("Spec constants overriding the preset, e.g. BASE_REWARD_FACTOR=128"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("n")
+                .help("Seed of the random number generator, random if not given"),
+        )
+}
+
+// options not given keep the value of the config, and so do the numbers
+//   which can not be parsed
+fn apply_matches(config: &mut Config, matches: &ArgMatches) -> Result<(), String> {
+    // first, as the stake and deposits are split by MAX_EFFECTIVE_BALANCE.
+    //   The preset is already set, with the scenario.
+    if let Some(overrides) = matches.value_of("spec_overrides") {
+        for (name, value) in SpecConstants::parse_overrides(overrides)? {
+            config.spec.set(&name, value)?;
+        }
+    }
+
+    if let Some(initial_stake) = matches.value_of("initial_stake") {
+        if let Ok(initial_stake) = initial_stake.trim().parse::<u64>() {
+            config.total_at_stake_initial = initial_stake * 1_000_000_000;
+        }
+    }
+
+    if let Some(queued_stake) = matches.value_of("queued_stake") {
+        if let Ok(queued_stake) = queued_stake.trim().parse::<u64>() {
+            config.total_at_stake_queued = queued_stake * 1_000_000_000;
+        }
+    }
+
+    if let Some(deposits_per_epoch) = matches.value_of("deposits_per_epoch") {
+        config.deposits_per_epoch = deposits_per_epoch
+            .trim()
+            .parse()
+            .unwrap_or(config.deposits_per_epoch);
+    }
+
+    if let Some(path) = matches.value_of("deposits_file") {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read deposits_file {}: {}", path, e))?;
+        config.deposit_schedule = Deposit::parse_csv(&contents, config.spec.max_effective_balance)?;
+    }
+
+    if let Some(epochs) = matches.value_of("epochs") {
+        config.epochs = epochs.trim().parse().unwrap_or(config.epochs);
+    }
+
+    if let Some(runs) = matches.value_of("runs") {
+        config.runs = runs.trim().parse().unwrap_or(config.runs);
+    }
+
+    if let Some(probability_online) = matches.value_of("probability_online") {
+        config.probability_online = probability_online
+            .trim()
+            .parse()
+            .unwrap_or(config.probability_online);
+    }
+
+    if let Some(probability_honest) = matches.value_of("probability_honest") {
+        config.probability_honest = probability_honest
+            .trim()
+            .parse()
+            .unwrap_or(config.probability_honest);
+    }
+
+    if let Some(dishonest_behaviour) = matches.value_of("dishonest_behaviour") {
+        config.dishonest_behaviour = Config::parse_dishonest_behaviour(dishonest_behaviour)?;
+    }
+
+    if let Some(probability_target) = matches.value_of("probability_target") {
+        config.probability_target = probability_target
+            .trim()
+            .parse()
+            .unwrap_or(config.probability_target);
+    }
+
+    if let Some(probability_head) = matches.value_of("probability_head") {
+        config.probability_head = probability_head
+            .trim()
+            .parse()
+            .unwrap_or(config.probability_head);
+    }
+
+    if let Some(printing_output) = matches.value_of("printing_output") {
+        config.printing_output = printing_output.to_string();
+    }
+
+    if let Some(probability_slashed) = matches.value_of("probability_slashed") {
+        config.probability_slashed = probability_slashed
+            .trim()
+            .parse()
+            .unwrap_or(config.probability_slashed);
+    }
+
+    if let Some(slashings) = matches.value_of("slashings") {
+        config.slashing_schedule = Config::parse_schedule(slashings)?;
+    }
+
+    if let Some(exit_policy) = matches.value_of("exit_policy") {
+        config.exit_policy = Config::parse_exit_policy(exit_policy)?;
+    }
+
+    if let Some(inclusion_delay) = matches.value_of("inclusion_delay") {
+        config.inclusion_delay = Config::parse_inclusion_delay(inclusion_delay)?;
+    }
+
+    if let Some(execution_rewards) = matches.value_of("execution_rewards") {
+        config.execution_rewards = Config::parse_execution_rewards(execution_rewards)?;
+    }
+
+    if let Some(path) = matches.value_of("execution_rewards_file") {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read execution_rewards_file {}: {}", path, e))?;
+        config.execution_rewards = ExecutionRewards::parse_csv(&contents)?;
+    }
+
+    if let Some(fork) = matches.value_of("fork") {
+        config.fork = Config::parse_fork(fork)?;
+    }
+
+    if let Some(engine) = matches.value_of("engine") {
+        config.engine = Config::parse_engine(engine)?;
+    }
+
+    if let Some(cohort_bucket) = matches.value_of("cohort_bucket") {
+        config.cohort_bucket = cohort_bucket.trim().parse().unwrap_or(config.cohort_bucket);
+    }
+
+    if let Some(seed) = matches.value_of("seed") {
+        config.seed = seed
+            .trim()
+            .parse()
+            .map_err(|_| "seed should be a non-negative integer".to_string())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_config_from_defaults() {
+        let config = get_config_from(vec!["simulation"]).unwrap();
+
+        assert_eq!(10, config.epochs);
+        assert_eq!(500_000_000_000_000, config.total_at_stake_initial);
+        assert_eq!(0.99, config.probability_online);
+        assert_eq!(Engine::Validators, config.engine);
+    }
+
+    #[test]
+    fn get_config_from_options() {
+        let config = get_config_from(vec![
+            "simulation",
+            "-e",
+            "20",
+            "-p",
+            "0.9",
+            "--engine",
+            "cohorts",
+            "--fork",
+            "altair",
+            "--seed",
+            "42",
+        ])
+        .unwrap();
+
+        assert_eq!(20, config.epochs);
+        assert_eq!(0.9, config.probability_online);
+        assert_eq!(Engine::Cohorts, config.engine);
+        assert_eq!(Fork::Altair, config.fork);
+        assert_eq!(42, config.seed);
+        assert_eq!(SpecConstants::mainnet(), config.spec);
+
+        // the pre-computation follows the options
+        assert_eq!(
+            Config::get_exp_value_inclusion_prob(0.9),
+            config.exp_value_inclusion_prob
+        );
+    }
+
+    #[test]
+    fn get_config_from_invalid_probability() {
+        assert_eq!(
+            Some("probability_online should be in the interval [0,1]".to_string()),
+            get_config_from(vec!["simulation", "-p", "1.5"]).err()
+        );
+    }
+
+    #[test]
+    fn get_config_from_scenario() {
+        let path = std::env::temp_dir().join("get_config_from_scenario.toml");
+        fs::write(
+            &path,
+            "epochs = 100\nseed = 42\n[probabilities]\nonline = 0.9\n",
+        )
+        .unwrap();
+
+        let config = get_config_from(vec![
+            "simulation",
+            "--scenario",
+            path.to_str().unwrap(),
+            "-e",
+            "20",
+        ])
+        .unwrap();
+
+        // the command line overrides the scenario
+        assert_eq!(20, config.epochs);
+        assert_eq!(0.9, config.probability_online);
+        assert_eq!(42, config.seed);
+    }
+
+    #[test]
+    fn get_config_from_spec() {
+        let config = get_config_from(vec![
+            "simulation",
+            "--spec",
+            "minimal",
+            "--spec_overrides",
+            "BASE_REWARD_FACTOR=128, MAX_EFFECTIVE_BALANCE=2048000000000",
+        ])
+        .unwrap();
+
+        assert_eq!(8, config.spec.slots_per_epoch);
+        assert_eq!(128, config.spec.base_reward_factor);
+        assert_eq!(2_048_000_000_000, config.spec.max_effective_balance);
+    }
+
+    #[test]
+    fn get_config_from_spec_and_scenario() {
+        let path = std::env::temp_dir().join("get_config_from_spec_and_scenario.toml");
+        fs::write(
+            &path,
+            "[spec]\npreset = \"mainnet\"\n\n[spec.overrides]\nBASE_REWARD_FACTOR = 128\nPROPOSER_REWARD_QUOTIENT = 4\n",
+        )
+        .unwrap();
+
+        let config = get_config_from(vec![
+            "simulation",
+            "--scenario",
+            path.to_str().unwrap(),
+            "--spec",
+            "minimal",
+            "--spec_overrides",
+            "PROPOSER_REWARD_QUOTIENT=16",
+        ])
+        .unwrap();
+
+        // the preset of the command line, under the overrides of the
+        //   scenario, under the ones of the command line
+        assert_eq!(8, config.spec.slots_per_epoch);
+        assert_eq!(128, config.spec.base_reward_factor);
+        assert_eq!(16, config.spec.proposer_reward_quotient);
+    }
+
+    #[test]
+    fn get_config_from_execution_rewards_file() {
+        let path = std::env::temp_dir().join("get_config_from_execution_rewards.csv");
+        fs::write(&path, "block,value\n15537394,0.05\n15537395,0.12\n").unwrap();
+
+        let config = get_config_from(vec![
+            "simulation",
+            "--execution_rewards",
+            "fixed:50000000",
+            "--execution_rewards_file",
+            path.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        // the file overrides the model
+        assert_eq!(
+            ExecutionRewards::Replay(vec![50_000_000, 120_000_000]),
+            config.execution_rewards
+        );
+    }
+}
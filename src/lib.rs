@@ -0,0 +1,12 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// simple simulator of rewards and penalties for Phase 0, as a library
+//
+////////////////////////////////////////////////////////////////////////////////
+
+pub mod cli;
+pub mod process_epoch;
+pub mod simulation;
+pub mod types;
+
+pub use simulation::*;
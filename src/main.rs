@@ -4,24 +4,34 @@
 //
 ////////////////////////////////////////////////////////////////////////////////
 
-mod process_epoch;
-mod types;
+use simulation::cli;
+use simulation::types::*;
+use simulation::Simulation;
 
-use process_epoch::process_epoch;
-use types::*;
+use std::process;
 
 fn main() {
-    let mut state = State::new();
-    let mut state_totals = StateTotals::new(&state);
-    let mut output = Output::new();
+    let config = cli::get_config().unwrap_or_else(|e| exit_with_error(&e));
 
-    for i in 0..state.config.epochs {
-        state = process_epoch(state, &mut state_totals, i, &mut output);
+    if config.runs > 1 {
+        let outputs = Simulation::run_monte_carlo(&config).unwrap_or_else(|e| exit_with_error(&e));
+
+        Output::print_monte_carlo_report(&outputs);
+        return;
     }
 
+    let (state, output) = Simulation::new(config)
+        .unwrap_or_else(|e| exit_with_error(&e))
+        .run();
+
     if state.config.printing_output == "monthly" {
         output.print_monthly_report(&state.config);
     } else if state.config.printing_output == "epoch" {
         output.print_epoch_report("csv");
     }
 }
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
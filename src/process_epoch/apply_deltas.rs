@@ -6,19 +6,22 @@
 
 use crate::types::*;
 
-pub fn apply_deltas(old_validator: &Validator, deltas: &Deltas) -> Validator {
-    Validator {
-        balance: old_validator.balance + deltas.head_ffg_reward - deltas.head_ffg_penalty
-            + deltas.proposer_reward
-            + deltas.attester_reward,
-        effective_balance: old_validator.effective_balance,
-        is_active: old_validator.is_active,
-        is_slashed: old_validator.is_slashed,
-        has_matched_source: old_validator.has_matched_source,
-        has_matched_head: old_validator.has_matched_head,
-        has_matched_target: old_validator.has_matched_target,
-        is_proposer: old_validator.is_proposer,
-    }
+pub fn apply_deltas(validator: &mut Validator, deltas: &Deltas) {
+    validator.balance = (validator.balance
+        + deltas.source_reward
+        + deltas.target_reward
+        + deltas.head_reward
+        + deltas.proposer_reward
+        + deltas.attester_reward
+        + deltas.sync_reward)
+        .saturating_sub(
+            deltas.source_penalty
+                + deltas.target_penalty
+                + deltas.head_penalty
+                + deltas.inactivity_penalty
+                + deltas.slashing_penalty
+                + deltas.sync_penalty,
+        );
 }
 
 // TODO: Test
@@ -4,28 +4,127 @@
 //
 ////////////////////////////////////////////////////////////////////////////////
 
+use super::get_flag_index_deltas::*;
+use super::sample_inclusion_delay::{is_included, Inclusion};
 use crate::types::*;
 
+// of the fork of the config
+pub fn get_base_reward(validator: &Validator, state: &State, state_totals: &StateTotals) -> u64 {
+    let sqrt_active_balance = state_totals.sqrt_active_balance;
+
+    match state.config.fork {
+        Fork::Phase0 => validator.get_base_reward(sqrt_active_balance, &state.config.spec),
+        Fork::Altair => validator.get_altair_base_reward(sqrt_active_balance, &state.config.spec),
+    }
+}
+
+// reward of the proposer including the attestation of the validator, when
+//   the inclusion delay is sampled
+pub fn get_inclusion_proposer_reward(
+    validator: &Validator,
+    base_reward: u64,
+    inclusion: &Inclusion,
+    config: &Config,
+) -> u64 {
+    match config.fork {
+        Fork::Phase0 => base_reward / config.spec.proposer_reward_quotient,
+        Fork::Altair => get_proposer_reward(
+            &ParticipationFlags::new(validator, Some(inclusion), config),
+            base_reward,
+            &config.spec,
+        ),
+    }
+}
+
 pub fn get_attestation_deltas(
     validator: &Validator,
     base_reward: u64,
     state: &State,
     state_totals: &StateTotals,
+    inclusion: Option<&Inclusion>,
     deltas: &mut Deltas,
 ) {
-    if !validator.is_active {
+    if !validator.is_eligible(state.get_previous_epoch()) {
         return;
     }
 
-    if !validator.has_matched_source {
-        assign_ffg_penalty(deltas, base_reward);
-    } else {
-        assign_ffg_reward(
-            deltas,
-            state_totals.adjusted_matching_balance,
-            state_totals.active_balance,
+    if state.config.fork == Fork::Altair {
+        get_flag_index_deltas(
+            validator,
             base_reward,
+            state,
+            state_totals,
+            inclusion,
+            deltas,
         );
+        return;
+    }
+
+    let spec = &state.config.spec;
+    let is_in_inactivity_leak = state.is_in_inactivity_leak();
+    let active_balance = state_totals.active_balance;
+
+    // SPEC
+    /*
+        def get_source_deltas(state: BeaconState) -> Tuple[Sequence[Gwei], Sequence[Gwei]]:
+            matching_source_attestations = get_matching_source_attestations(state, get_previous_epoch(state))
+            return get_attestation_component_deltas(state, matching_source_attestations)
+
+        def get_target_deltas(state: BeaconState) -> Tuple[Sequence[Gwei], Sequence[Gwei]]:
+            matching_target_attestations = get_matching_target_attestations(state, get_previous_epoch(state))
+            return get_attestation_component_deltas(state, matching_target_attestations)
+
+        def get_head_deltas(state: BeaconState) -> Tuple[Sequence[Gwei], Sequence[Gwei]]:
+            matching_head_attestations = get_matching_head_attestations(state, get_previous_epoch(state))
+            return get_attestation_component_deltas(state, matching_head_attestations)
+    */
+
+    // an attestation never included is in none of the matching sets
+    let is_included = is_included(inclusion, &state.config);
+
+    let (source_reward, source_penalty) = get_attestation_component_deltas(
+        validator.has_matched_source && is_included,
+        state_totals.source_attesting_balance,
+        active_balance,
+        base_reward,
+        is_in_inactivity_leak,
+        spec,
+    );
+    deltas.source_reward = source_reward;
+    deltas.source_penalty = source_penalty;
+
+    let (target_reward, target_penalty) = get_attestation_component_deltas(
+        validator.has_matched_target && is_included,
+        state_totals.target_attesting_balance,
+        active_balance,
+        base_reward,
+        is_in_inactivity_leak,
+        spec,
+    );
+    deltas.target_reward = target_reward;
+    deltas.target_penalty = target_penalty;
+
+    let (head_reward, head_penalty) = get_attestation_component_deltas(
+        validator.has_matched_head && is_included,
+        state_totals.head_attesting_balance,
+        active_balance,
+        base_reward,
+        is_in_inactivity_leak,
+        spec,
+    );
+    deltas.head_reward = head_reward;
+    deltas.head_penalty = head_penalty;
+
+    // inclusion rewards only depend on the attestation being included,
+    //   that is, on a matching source
+    if validator.has_matched_source {
+        if state.config.inclusion_delay != InclusionDelay::Expected {
+            // the including proposer is credited by the caller
+            if let Some(inclusion) = inclusion {
+                assign_sampled_attester_incentive(deltas, inclusion.delay, base_reward, spec);
+            }
+            return;
+        }
 
         if validator.is_proposer {
             assign_proposer_incentive(
@@ -33,34 +132,54 @@ pub fn get_attestation_deltas(
                 state_totals.active_validators,
                 state.config.probability_online,
                 base_reward,
+                spec,
             );
         }
 
-        assign_attester_incentive(deltas, state.config.exp_value_inclusion_prob, base_reward);
+        assign_attester_incentive(
+            deltas,
+            state.config.exp_value_inclusion_prob,
+            base_reward,
+            spec,
+        );
     }
 }
 
-fn assign_ffg_reward(
-    deltas: &mut Deltas,
-    adjusted_matching_balance: u64,
+// returns (reward, penalty) for one of the source, target and head votes
+fn get_attestation_component_deltas(
+    has_matched: bool,
+    attesting_balance: u64,
     active_balance: u64,
     base_reward: u64,
-) {
+    is_in_inactivity_leak: bool,
+    spec: &SpecConstants,
+) -> (u64, u64) {
     // SPEC
     /*
-        increment = EFFECTIVE_BALANCE_INCREMENT  # Factored out from balance totals to avoid uint64 overflow
-        reward_numerator = get_base_reward(state, index) * (attesting_balance // increment)
-        rewards[index] = reward_numerator // (total_balance // increment)
+        if index in unslashed_attesting_indices:
+            increment = EFFECTIVE_BALANCE_INCREMENT  # Factored out from balance totals to avoid uint64 overflow
+            if is_in_inactivity_leak(state):
+                # Since full base reward will be canceled out by inactivity penalty deltas,
+                # optimal participation receives full base reward compensation here.
+                rewards[index] += get_base_reward(state, index)
+            else:
+                reward_numerator = get_base_reward(state, index) * (attesting_balance // increment)
+                rewards[index] += reward_numerator // (total_balance // increment)
+        else:
+            penalties[index] += get_base_reward(state, index)
     */
 
-    let increment = config::EFFECTIVE_BALANCE_INCREMENT;
-    let reward_numerator = base_reward * (adjusted_matching_balance / increment);
-    let reward = reward_numerator / (active_balance / increment);
-    deltas.head_ffg_reward = 3 * reward;
-}
+    if !has_matched {
+        return (0, base_reward);
+    }
 
-fn assign_ffg_penalty(deltas: &mut Deltas, base_reward: u64) {
-    deltas.head_ffg_penalty = 3 * base_reward;
+    if is_in_inactivity_leak {
+        return (base_reward, 0);
+    }
+
+    let increment = spec.effective_balance_increment;
+    let reward_numerator = base_reward * (attesting_balance / increment);
+    (reward_numerator / (active_balance / increment), 0)
 }
 
 fn assign_proposer_incentive(
@@ -68,28 +187,52 @@ fn assign_proposer_incentive(
     active_validators: u64,
     probability_online: f32,
     base_reward: u64,
+    spec: &SpecConstants,
 ) {
-    let proposer_reward_amount = base_reward / config::PROPOSER_REWARD_QUOTIENT;
-    let number_of_attesters = active_validators / 32;
+    let proposer_reward_amount = base_reward / spec.proposer_reward_quotient;
+    let number_of_attesters = active_validators / spec.slots_per_epoch;
     let number_of_attestations = (number_of_attesters as f32 * probability_online).floor() as u64;
 
     deltas.proposer_reward = proposer_reward_amount * number_of_attestations;
 }
 
-fn assign_attester_incentive(deltas: &mut Deltas, magic_number: f32, base_reward: u64) {
-    let proposer_reward_amount = base_reward / config::PROPOSER_REWARD_QUOTIENT;
+fn assign_attester_incentive(
+    deltas: &mut Deltas,
+    magic_number: f32,
+    base_reward: u64,
+    spec: &SpecConstants,
+) {
+    let proposer_reward_amount = base_reward / spec.proposer_reward_quotient;
     let maximum_attester_reward = base_reward - proposer_reward_amount;
 
     deltas.attester_reward = (maximum_attester_reward as f32 * magic_number).floor() as u64;
 }
 
+fn assign_sampled_attester_incentive(
+    deltas: &mut Deltas,
+    inclusion_delay: u64,
+    base_reward: u64,
+    spec: &SpecConstants,
+) {
+    // SPEC
+    /*
+        max_attester_reward = get_base_reward(state, index) - proposer_reward
+        rewards[index] += Gwei(max_attester_reward // attestation.inclusion_delay)
+    */
+
+    let proposer_reward_amount = base_reward / spec.proposer_reward_quotient;
+    let maximum_attester_reward = base_reward - proposer_reward_amount;
+
+    deltas.attester_reward = maximum_attester_reward / inclusion_delay;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn non_eligible_validator() {
-        let mut state = State::new();
+        let mut state = State::from_config(Config::default());
         let state_totals = StateTotals::new(&state);
         let mut deltas = Deltas::new();
 
@@ -97,82 +240,162 @@ mod tests {
 
         get_attestation_deltas(
             &state.validators[0],
-            state.validators[0].get_base_reward(state_totals.sqrt_active_balance),
+            state.validators[0]
+                .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec),
             &state,
             &state_totals,
+            None,
             &mut deltas,
         );
 
-        assert_eq!(0, deltas.head_ffg_reward);
-        assert_eq!(0, deltas.head_ffg_penalty);
+        assert_eq!(0, deltas.source_reward);
+        assert_eq!(0, deltas.source_penalty);
+        assert_eq!(0, deltas.target_penalty);
+        assert_eq!(0, deltas.head_penalty);
         assert_eq!(0, deltas.proposer_reward);
         assert_eq!(0, deltas.attester_reward);
     }
 
     #[test]
     fn slashed_validator() {
-        let mut state = State::new();
+        let mut state = State::from_config(Config::default());
         let state_totals = StateTotals::new(&state);
         let mut deltas = Deltas::new();
 
         // our validator has the slashed status
         state.validators[0].is_slashed = true;
-        let base_reward = state.validators[0].get_base_reward(state_totals.sqrt_active_balance);
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
 
         get_attestation_deltas(
             &state.validators[0],
             base_reward,
             &state,
             &state_totals,
+            None,
             &mut deltas,
         );
 
-        assert_eq!(0, deltas.head_ffg_reward);
-        assert_eq!(68691, deltas.head_ffg_penalty);
-        assert_eq!(3 * base_reward, deltas.head_ffg_penalty);
+        assert_eq!(0, deltas.source_reward);
+        assert_eq!(22897, deltas.source_penalty);
+        assert_eq!(base_reward, deltas.source_penalty);
+        assert_eq!(base_reward, deltas.target_penalty);
+        assert_eq!(base_reward, deltas.head_penalty);
         assert_eq!(0, deltas.proposer_reward);
         assert_eq!(0, deltas.attester_reward);
     }
 
     #[test]
-    fn ffg_rewards_1() {
-        let mut state = State::new();
+    fn vote_rewards() {
+        let mut state = State::from_config(Config::default());
         let state_totals = StateTotals::new(&state);
         let mut deltas = Deltas::new();
 
         state.config.probability_online = 1.0;
         state.validators[0].is_active = true;
         state.validators[0].has_matched_source = true;
-        let base_reward = state.validators[0].get_base_reward(state_totals.sqrt_active_balance);
+        state.validators[0].has_matched_target = true;
+        state.validators[0].has_matched_head = true;
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
 
         get_attestation_deltas(
             &state.validators[0],
             base_reward,
             &state,
             &state_totals,
+            None,
             &mut deltas,
         );
 
-        assert_eq!(68004, deltas.head_ffg_reward);
-        assert_eq!(0, deltas.head_ffg_penalty);
+        assert_eq!(22668, deltas.source_reward);
+        assert_eq!(22668, deltas.target_reward);
+        assert_eq!(22668, deltas.head_reward);
+        assert_eq!(0, deltas.source_penalty);
+        assert_eq!(0, deltas.target_penalty);
+        assert_eq!(0, deltas.head_penalty);
+    }
+
+    #[test]
+    fn vote_rewards_missed_head() {
+        let mut state = State::from_config(Config::default());
+        state.config.probability_online = 1.0;
+        state.config.probability_head = 0.5;
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+
+        // the head vote fails more often, so the head attesting balance is lower
+        assert_eq!(
+            state_totals.target_attesting_balance / 2,
+            state_totals.head_attesting_balance
+        );
+
+        state.validators[0].has_matched_source = true;
+        state.validators[0].has_matched_target = true;
+        state.validators[0].has_matched_head = false;
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+
+        get_attestation_deltas(
+            &state.validators[0],
+            base_reward,
+            &state,
+            &state_totals,
+            None,
+            &mut deltas,
+        );
+
+        assert_eq!(base_reward, deltas.source_reward);
+        assert_eq!(base_reward, deltas.target_reward);
+        assert_eq!(0, deltas.head_reward);
+        assert_eq!(base_reward, deltas.head_penalty);
+    }
+
+    #[test]
+    fn vote_rewards_inactivity_leak() {
+        let mut state = State::from_config(Config::default());
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+
+        state.current_epoch = 10;
+        state.validators[0].has_matched_source = true;
+        state.validators[0].has_matched_target = true;
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+
+        get_attestation_deltas(
+            &state.validators[0],
+            base_reward,
+            &state,
+            &state_totals,
+            None,
+            &mut deltas,
+        );
+
+        assert_eq!(base_reward, deltas.source_reward);
+        assert_eq!(base_reward, deltas.target_reward);
+        assert_eq!(0, deltas.head_reward);
+        assert_eq!(base_reward, deltas.head_penalty);
     }
 
     #[test]
     fn proposer_reward_validator_is_proposer() {
-        let mut state = State::new();
+        let mut state = State::from_config(Config::default());
         let state_totals = StateTotals::new(&state);
         let mut deltas = Deltas::new();
 
         state.config.probability_online = 1.0;
         state.validators[0].has_matched_source = true;
         state.validators[0].is_proposer = true;
-        let base_reward = state.validators[0].get_base_reward(state_totals.sqrt_active_balance);
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
 
         get_attestation_deltas(
             &state.validators[0],
             base_reward,
             &state,
             &state_totals,
+            None,
             &mut deltas,
         );
 
@@ -181,19 +404,21 @@ mod tests {
 
     #[test]
     fn proposer_reward_validator_is_not_proposer() {
-        let mut state = State::new();
+        let mut state = State::from_config(Config::default());
         let state_totals = StateTotals::new(&state);
         let mut deltas = Deltas::new();
 
         state.validators[0].has_matched_source = true;
         state.validators[0].is_proposer = false;
-        let base_reward = state.validators[0].get_base_reward(state_totals.sqrt_active_balance);
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
 
         get_attestation_deltas(
             &state.validators[0],
             base_reward,
             &state,
             &state_totals,
+            None,
             &mut deltas,
         );
 
@@ -202,23 +427,89 @@ mod tests {
 
     #[test]
     fn attester_reward() {
-        let mut state = State::new();
+        let mut state = State::from_config(Config::default());
         let state_totals = StateTotals::new(&state);
         let mut deltas = Deltas::new();
 
         state.config.probability_online = 1.0;
         state.config.exp_value_inclusion_prob = 1.0;
         state.validators[0].has_matched_source = true;
-        let base_reward = state.validators[0].get_base_reward(state_totals.sqrt_active_balance);
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
 
         get_attestation_deltas(
             &state.validators[0],
             base_reward,
             &state,
             &state_totals,
+            None,
             &mut deltas,
         );
 
         assert_eq!(20_035, deltas.attester_reward);
     }
+
+    #[test]
+    fn sampled_attester_reward() {
+        let mut state = State::from_config(Config::default());
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+
+        state.config.inclusion_delay = InclusionDelay::Sampled;
+        state.validators[0].has_matched_source = true;
+        state.validators[0].is_proposer = true;
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+        let inclusion = Inclusion {
+            delay: 2,
+            proposer_slot: 0,
+        };
+
+        get_attestation_deltas(
+            &state.validators[0],
+            base_reward,
+            &state,
+            &state_totals,
+            Some(&inclusion),
+            &mut deltas,
+        );
+
+        // (22_897 - 22_897 / 8) / 2
+        assert_eq!(10_017, deltas.attester_reward);
+
+        // proposers are credited for the attestations they include, not here
+        assert_eq!(0, deltas.proposer_reward);
+    }
+
+    #[test]
+    fn sampled_attestation_not_included() {
+        let mut state = State::from_config(Config::default());
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+
+        // no proposer was online to include the attestation
+        state.config.inclusion_delay = InclusionDelay::Sampled;
+        state.validators[0].has_matched_source = true;
+        state.validators[0].has_matched_target = true;
+        state.validators[0].has_matched_head = true;
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+
+        get_attestation_deltas(
+            &state.validators[0],
+            base_reward,
+            &state,
+            &state_totals,
+            None,
+            &mut deltas,
+        );
+
+        assert_eq!(0, deltas.source_reward);
+        assert_eq!(0, deltas.target_reward);
+        assert_eq!(0, deltas.head_reward);
+        assert_eq!(base_reward, deltas.source_penalty);
+        assert_eq!(base_reward, deltas.target_penalty);
+        assert_eq!(base_reward, deltas.head_penalty);
+        assert_eq!(0, deltas.attester_reward);
+    }
 }
@@ -0,0 +1,352 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates the Altair `get_flag_index_deltas` of `process_rewards_and_penalties`
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use super::sample_inclusion_delay::Inclusion;
+use crate::types::*;
+use integer_sqrt::IntegerSquareRoot;
+
+// the participation flags set by the attestation of a validator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticipationFlags {
+    pub timely_source: bool,
+    pub timely_target: bool,
+    pub timely_head: bool,
+}
+
+impl ParticipationFlags {
+    // SPEC: get_attestation_participation_flag_indices
+    pub fn new(
+        validator: &Validator,
+        inclusion: Option<&Inclusion>,
+        config: &Config,
+    ) -> ParticipationFlags {
+        // SPEC
+        /*
+            participation_flag_indices = []
+            if is_matching_source and inclusion_delay <= integer_squareroot(SLOTS_PER_EPOCH):
+                participation_flag_indices.append(TIMELY_SOURCE_FLAG_INDEX)
+            if is_matching_target and inclusion_delay <= SLOTS_PER_EPOCH:
+                participation_flag_indices.append(TIMELY_TARGET_FLAG_INDEX)
+            if is_matching_head and inclusion_delay == MIN_ATTESTATION_INCLUSION_DELAY:
+                participation_flag_indices.append(TIMELY_HEAD_FLAG_INDEX)
+        */
+
+        // with the expected inclusion delay, attestations are included in the
+        //   next slot. Otherwise, an attestation not included sets no flag.
+        let inclusion_delay = match (&config.inclusion_delay, inclusion) {
+            (InclusionDelay::Expected, _) => config::MIN_ATTESTATION_INCLUSION_DELAY,
+            (_, Some(inclusion)) => inclusion.delay,
+            (_, None) => u64::MAX,
+        };
+        let slots_per_epoch = config.spec.slots_per_epoch;
+
+        ParticipationFlags {
+            timely_source: validator.has_matched_source
+                && inclusion_delay <= slots_per_epoch.integer_sqrt(),
+            timely_target: validator.has_matched_target && inclusion_delay <= slots_per_epoch,
+            timely_head: validator.has_matched_head
+                && inclusion_delay == config::MIN_ATTESTATION_INCLUSION_DELAY,
+        }
+    }
+}
+
+// the validator is eligible, as checked by `get_attestation_deltas`
+pub fn get_flag_index_deltas(
+    validator: &Validator,
+    base_reward: u64,
+    state: &State,
+    state_totals: &StateTotals,
+    inclusion: Option<&Inclusion>,
+    deltas: &mut Deltas,
+) {
+    let spec = &state.config.spec;
+    let flags = ParticipationFlags::new(validator, inclusion, &state.config);
+    let is_in_inactivity_leak = state.is_in_inactivity_leak();
+    let active_increments = state_totals.active_balance / spec.effective_balance_increment;
+
+    let get_component_deltas = |is_participating: bool, weight: u64, participating_balance: u64| {
+        // SPEC
+        /*
+            unslashed_participating_increments = unslashed_participating_balance // EFFECTIVE_BALANCE_INCREMENT
+            active_increments = get_total_active_balance(state) // EFFECTIVE_BALANCE_INCREMENT
+            for index in get_eligible_validator_indices(state):
+                base_reward = get_base_reward(state, index)
+                if index in unslashed_participating_indices:
+                    if not is_in_inactivity_leak(state):
+                        reward_numerator = base_reward * weight * unslashed_participating_increments
+                        rewards[index] += Gwei(reward_numerator // (active_increments * WEIGHT_DENOMINATOR))
+                elif flag_index != TIMELY_HEAD_FLAG_INDEX:
+                    penalties[index] += Gwei(base_reward * weight // WEIGHT_DENOMINATOR)
+        */
+
+        if !is_participating {
+            return (0, base_reward * weight / spec.weight_denominator);
+        }
+
+        if is_in_inactivity_leak {
+            return (0, 0);
+        }
+
+        let participating_increments = participating_balance / spec.effective_balance_increment;
+        let reward_numerator = base_reward * weight * participating_increments;
+        (
+            reward_numerator / (active_increments * spec.weight_denominator),
+            0,
+        )
+    };
+
+    let (source_reward, source_penalty) = get_component_deltas(
+        flags.timely_source,
+        spec.timely_source_weight,
+        state_totals.source_attesting_balance,
+    );
+    deltas.source_reward = source_reward;
+    deltas.source_penalty = source_penalty;
+
+    let (target_reward, target_penalty) = get_component_deltas(
+        flags.timely_target,
+        spec.timely_target_weight,
+        state_totals.target_attesting_balance,
+    );
+    deltas.target_reward = target_reward;
+    deltas.target_penalty = target_penalty;
+
+    // a late or wrong head is not penalized
+    let (head_reward, _) = get_component_deltas(
+        flags.timely_head,
+        spec.timely_head_weight,
+        state_totals.head_attesting_balance,
+    );
+    deltas.head_reward = head_reward;
+
+    // with a sampled inclusion delay, the including proposer is credited by
+    //   the caller. The proposer is online if its own attestation was included.
+    if validator.is_proposer
+        && validator.has_matched_source
+        && state.config.inclusion_delay == InclusionDelay::Expected
+    {
+        deltas.proposer_reward = get_expected_proposer_reward(base_reward, state_totals, spec);
+    }
+}
+
+// SPEC: process_attestation of Altair, for the attestation of a validator
+//   setting the given flags
+pub fn get_proposer_reward(
+    flags: &ParticipationFlags,
+    base_reward: u64,
+    spec: &SpecConstants,
+) -> u64 {
+    // SPEC
+    /*
+        proposer_reward_numerator = 0
+        for index in get_attesting_indices(state, data, attestation.aggregation_bits):
+            for flag_index, weight in enumerate(PARTICIPATION_FLAG_WEIGHTS):
+                if flag_index in participation_flag_indices and not has_flag(epoch_participation[index], flag_index):
+                    epoch_participation[index] = add_flag(epoch_participation[index], flag_index)
+                    proposer_reward_numerator += get_base_reward(state, index) * weight
+
+        proposer_reward_denominator = (WEIGHT_DENOMINATOR - PROPOSER_WEIGHT) * WEIGHT_DENOMINATOR // PROPOSER_WEIGHT
+        proposer_reward = Gwei(proposer_reward_numerator // proposer_reward_denominator)
+    */
+
+    let proposer_reward_numerator: u64 = [
+        (flags.timely_source, spec.timely_source_weight),
+        (flags.timely_target, spec.timely_target_weight),
+        (flags.timely_head, spec.timely_head_weight),
+    ]
+    .iter()
+    .filter(|(is_set, _)| *is_set)
+    .map(|(_, weight)| base_reward * weight)
+    .sum();
+
+    proposer_reward_numerator / get_proposer_reward_denominator(spec)
+}
+
+// the block of the proposer includes the attestations of a slot, each
+//   attester setting the flags with the probability given by the attesting
+//   balances. As in Phase 0, the attesters have the base reward of the proposer.
+fn get_expected_proposer_reward(
+    base_reward: u64,
+    state_totals: &StateTotals,
+    spec: &SpecConstants,
+) -> u64 {
+    let number_of_attesters = (state_totals.active_validators / spec.slots_per_epoch) as f64;
+    let active_balance = state_totals.active_balance as f64;
+
+    let proposer_reward_numerator: f64 = [
+        (
+            state_totals.source_attesting_balance,
+            spec.timely_source_weight,
+        ),
+        (
+            state_totals.target_attesting_balance,
+            spec.timely_target_weight,
+        ),
+        (state_totals.head_attesting_balance, spec.timely_head_weight),
+    ]
+    .iter()
+    .map(|(attesting_balance, weight)| {
+        number_of_attesters
+            * (*attesting_balance as f64 / active_balance)
+            * (base_reward * weight) as f64
+    })
+    .sum();
+
+    (proposer_reward_numerator / get_proposer_reward_denominator(spec) as f64).floor() as u64
+}
+
+fn get_proposer_reward_denominator(spec: &SpecConstants) -> u64 {
+    (spec.weight_denominator - spec.proposer_weight) * spec.weight_denominator
+        / spec.proposer_weight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_altair_state(inclusion_delay: InclusionDelay) -> State {
+        State::from_config(Config {
+            fork: Fork::Altair,
+            probability_online: 1.0,
+            inclusion_delay,
+            ..Config::default()
+        })
+    }
+
+    fn set_votes(validator: &mut Validator, source: bool, target: bool, head: bool) {
+        validator.has_matched_source = source;
+        validator.has_matched_target = target;
+        validator.has_matched_head = head;
+    }
+
+    #[test]
+    fn participation_flags_timeliness() {
+        let mut state = get_altair_state(InclusionDelay::Sampled);
+        set_votes(&mut state.validators[0], true, true, true);
+
+        // (inclusion delay, expected source, target, head)
+        let cases = vec![
+            (1, (true, true, true)),
+            (2, (true, true, false)),
+            (5, (true, true, false)),
+            (6, (false, true, false)),
+            (32, (false, true, false)),
+        ];
+
+        for (delay, expected_result) in cases {
+            let inclusion = Inclusion {
+                delay,
+                proposer_slot: 0,
+            };
+            let flags =
+                ParticipationFlags::new(&state.validators[0], Some(&inclusion), &state.config);
+
+            assert_eq!(
+                expected_result,
+                (flags.timely_source, flags.timely_target, flags.timely_head)
+            );
+        }
+
+        // an attestation never included sets no flag
+        let flags = ParticipationFlags::new(&state.validators[0], None, &state.config);
+        assert_eq!(
+            (false, false, false),
+            (flags.timely_source, flags.timely_target, flags.timely_head)
+        );
+    }
+
+    #[test]
+    fn full_participation() {
+        let mut state = get_altair_state(InclusionDelay::Expected);
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+        set_votes(&mut state.validators[0], true, true, true);
+
+        let base_reward = state.validators[0]
+            .get_altair_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+        get_flag_index_deltas(
+            &state.validators[0],
+            base_reward,
+            &state,
+            &state_totals,
+            None,
+            &mut deltas,
+        );
+
+        // every validator participates, so each flag pays its full weight
+        assert_eq!(base_reward * 14 / 64, deltas.source_reward);
+        assert_eq!(base_reward * 26 / 64, deltas.target_reward);
+        assert_eq!(base_reward * 14 / 64, deltas.head_reward);
+        assert_eq!(0, deltas.source_penalty + deltas.target_penalty);
+        assert_eq!(0, deltas.attester_reward);
+    }
+
+    #[test]
+    fn missed_votes() {
+        let mut state = get_altair_state(InclusionDelay::Expected);
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+        set_votes(&mut state.validators[0], false, false, false);
+
+        let base_reward = state.validators[0]
+            .get_altair_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+        get_flag_index_deltas(
+            &state.validators[0],
+            base_reward,
+            &state,
+            &state_totals,
+            None,
+            &mut deltas,
+        );
+
+        assert_eq!(0, deltas.source_reward + deltas.target_reward);
+        assert_eq!(base_reward * 14 / 64, deltas.source_penalty);
+        assert_eq!(base_reward * 26 / 64, deltas.target_penalty);
+        assert_eq!(0, deltas.head_penalty);
+    }
+
+    #[test]
+    fn inactivity_leak() {
+        let mut state = get_altair_state(InclusionDelay::Expected);
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+        state.current_epoch = 10;
+        set_votes(&mut state.validators[0], true, false, false);
+
+        let base_reward = state.validators[0]
+            .get_altair_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+        get_flag_index_deltas(
+            &state.validators[0],
+            base_reward,
+            &state,
+            &state_totals,
+            None,
+            &mut deltas,
+        );
+
+        // no rewards during the leak, the penalties stay
+        assert_eq!(0, deltas.source_reward);
+        assert_eq!(base_reward * 26 / 64, deltas.target_penalty);
+    }
+
+    #[test]
+    fn proposer_reward() {
+        let spec = SpecConstants::mainnet();
+        let flags = ParticipationFlags {
+            timely_source: true,
+            timely_target: true,
+            timely_head: true,
+        };
+
+        // the proposer gets PROPOSER_WEIGHT / (WEIGHT_DENOMINATOR - PROPOSER_WEIGHT)
+        //   of the rewards of the attesters
+        assert_eq!(54, get_proposer_reward(&flags, 448, &spec));
+        assert_eq!(
+            get_proposer_reward(&flags, 448_000, &spec) * 7,
+            448_000 * 54 / 64
+        );
+    }
+}
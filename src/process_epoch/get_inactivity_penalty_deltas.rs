@@ -0,0 +1,213 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates the inactivity leak of `process_rewards_and_penalties`
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use super::get_flag_index_deltas::ParticipationFlags;
+use super::sample_inclusion_delay::{is_included, Inclusion};
+use crate::types::*;
+
+pub fn get_inactivity_penalty_deltas(
+    validator: &Validator,
+    base_reward: u64,
+    state: &State,
+    inclusion: Option<&Inclusion>,
+    deltas: &mut Deltas,
+) {
+    if !validator.is_eligible(state.get_previous_epoch()) {
+        return;
+    }
+
+    // the inactivity score outlasts the leak
+    if state.config.fork == Fork::Altair {
+        get_altair_inactivity_penalty_deltas(validator, state, inclusion, deltas);
+        return;
+    }
+
+    if !state.is_in_inactivity_leak() {
+        return;
+    }
+
+    // SPEC
+    /*
+        # If validator is performing optimally this cancels all rewards for a neutral balance
+        base_reward = get_base_reward(state, index)
+        penalties[index] += Gwei(BASE_REWARDS_PER_EPOCH * base_reward - get_proposer_reward(state, index))
+        if index not in matching_target_attesting_indices:
+            effective_balance = state.validators[index].effective_balance
+            penalties[index] += Gwei(effective_balance * get_finality_delay(state) // INACTIVITY_PENALTY_QUOTIENT)
+    */
+
+    let spec = &state.config.spec;
+    let proposer_reward = base_reward / spec.proposer_reward_quotient;
+    deltas.inactivity_penalty = spec.base_rewards_per_epoch * base_reward - proposer_reward;
+
+    if !validator.has_matched_target || !is_included(inclusion, &state.config) {
+        deltas.inactivity_penalty += validator.effective_balance * state.get_finality_delay()
+            / spec.inactivity_penalty_quotient;
+    }
+}
+
+// Altair pays no rewards during the leak, so there is nothing to cancel out.
+//   The score is already updated by `process_inactivity_updates`.
+fn get_altair_inactivity_penalty_deltas(
+    validator: &Validator,
+    state: &State,
+    inclusion: Option<&Inclusion>,
+    deltas: &mut Deltas,
+) {
+    // SPEC
+    /*
+        for index in get_eligible_validator_indices(state):
+            if index not in matching_target_indices:
+                penalty_numerator = state.validators[index].effective_balance * state.inactivity_scores[index]
+                penalty_denominator = INACTIVITY_SCORE_BIAS * INACTIVITY_PENALTY_QUOTIENT_ALTAIR
+                penalties[index] += Gwei(penalty_numerator // penalty_denominator)
+    */
+
+    let spec = &state.config.spec;
+    let flags = ParticipationFlags::new(validator, inclusion, &state.config);
+    if !flags.timely_target {
+        deltas.inactivity_penalty = validator.effective_balance * validator.inactivity_score
+            / (spec.inactivity_score_bias * spec.inactivity_penalty_quotient_altair);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_inactivity_leak() {
+        let mut state = State::from_config(Config::default());
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+
+        state.current_epoch = 10;
+        state.finalized_epoch = 8;
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+
+        get_inactivity_penalty_deltas(&state.validators[0], base_reward, &state, None, &mut deltas);
+
+        assert_eq!(0, deltas.inactivity_penalty);
+    }
+
+    #[test]
+    fn inactivity_leak_matched_target() {
+        let mut state = State::from_config(Config::default());
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+
+        state.current_epoch = 10;
+        state.validators[0].has_matched_target = true;
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+
+        get_inactivity_penalty_deltas(&state.validators[0], base_reward, &state, None, &mut deltas);
+
+        assert_eq!(4 * base_reward - base_reward / 8, deltas.inactivity_penalty);
+    }
+
+    #[test]
+    fn inactivity_leak_missed_target() {
+        let mut state = State::from_config(Config::default());
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+
+        // finality delay of 9 epochs
+        state.current_epoch = 10;
+        state.validators[0].has_matched_target = false;
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+
+        get_inactivity_penalty_deltas(&state.validators[0], base_reward, &state, None, &mut deltas);
+
+        assert_eq!(
+            4 * base_reward - base_reward / 8 + 32_000_000_000 * 9 / 67_108_864,
+            deltas.inactivity_penalty
+        );
+    }
+
+    #[test]
+    fn altair_inactivity_score() {
+        let mut state = State::from_config(Config {
+            fork: Fork::Altair,
+            ..Config::default()
+        });
+        let mut deltas = Deltas::new();
+
+        state.current_epoch = 10;
+        state.validators[0].inactivity_score = 36;
+        state.validators[0].has_matched_target = true;
+        get_inactivity_penalty_deltas(&state.validators[0], 0, &state, None, &mut deltas);
+        assert_eq!(0, deltas.inactivity_penalty);
+
+        state.validators[0].has_matched_target = false;
+        get_inactivity_penalty_deltas(&state.validators[0], 0, &state, None, &mut deltas);
+        assert_eq!(
+            32_000_000_000 * 36 / (4 * 50_331_648),
+            deltas.inactivity_penalty
+        );
+
+        // still penalized after the leak, until the score recovers
+        state.finalized_epoch = 8;
+        get_inactivity_penalty_deltas(&state.validators[0], 0, &state, None, &mut deltas);
+        assert_eq!(
+            32_000_000_000 * 36 / (4 * 50_331_648),
+            deltas.inactivity_penalty
+        );
+    }
+
+    #[test]
+    fn altair_late_target() {
+        let mut state = State::from_config(Config {
+            fork: Fork::Altair,
+            inclusion_delay: InclusionDelay::Sampled,
+            ..Config::default()
+        });
+        let mut deltas = Deltas::new();
+
+        state.current_epoch = 10;
+        state.validators[0].inactivity_score = 36;
+        state.validators[0].has_matched_target = true;
+
+        // the matching target of an attestation never included is not timely
+        get_inactivity_penalty_deltas(&state.validators[0], 0, &state, None, &mut deltas);
+        assert_eq!(
+            32_000_000_000 * 36 / (4 * 50_331_648),
+            deltas.inactivity_penalty
+        );
+
+        let inclusion = Inclusion {
+            delay: 1,
+            proposer_slot: 0,
+        };
+        let mut deltas = Deltas::new();
+        get_inactivity_penalty_deltas(
+            &state.validators[0],
+            0,
+            &state,
+            Some(&inclusion),
+            &mut deltas,
+        );
+        assert_eq!(0, deltas.inactivity_penalty);
+    }
+
+    #[test]
+    fn inactive_validator() {
+        let mut state = State::from_config(Config::default());
+        let state_totals = StateTotals::new(&state);
+        let mut deltas = Deltas::new();
+
+        state.current_epoch = 10;
+        state.validators[0].is_active = false;
+        let base_reward = state.validators[0]
+            .get_base_reward(state_totals.sqrt_active_balance, &state.config.spec);
+
+        get_inactivity_penalty_deltas(&state.validators[0], base_reward, &state, None, &mut deltas);
+
+        assert_eq!(0, deltas.inactivity_penalty);
+    }
+}
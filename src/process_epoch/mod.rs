@@ -6,57 +6,287 @@
 
 mod apply_deltas;
 mod get_attestation_deltas;
+mod get_flag_index_deltas;
+mod get_inactivity_penalty_deltas;
+mod process_analytic;
+mod process_cohorts;
+mod process_deposits;
+mod process_execution_rewards;
+mod process_inactivity_updates;
+mod process_justification_and_finalization;
+mod process_registry_updates;
+mod process_slashings;
+mod process_sync_aggregate;
+mod process_voluntary_exits;
+mod sample_inclusion_delay;
 
 use crate::types::*;
 use apply_deltas::*;
 use get_attestation_deltas::*;
+use get_inactivity_penalty_deltas::*;
+pub use process_analytic::process_epoch_analytic;
+pub use process_cohorts::process_epoch_cohorts;
+use process_deposits::*;
+use process_execution_rewards::*;
+use process_inactivity_updates::*;
+use process_justification_and_finalization::*;
+use process_registry_updates::*;
+use process_slashings::*;
+use process_sync_aggregate::*;
+use process_voluntary_exits::*;
+use rand::prelude::*;
+use rayon::prelude::*;
+use sample_inclusion_delay::*;
+
+// validators processed together by a thread, with the same random number generator
+const VALIDATORS_PER_CHUNK: usize = 4_096;
+
+// outcome of the processing of a chunk of validators, to be reduced
+struct ChunkResult {
+    // sum of the deltas of the validators of the chunk
+    deltas: Deltas,
+
+    // totals of the validators of the chunk, once processed
+    totals: StateTotals,
+
+    // rewards of the proposers of each slot for the attestations they include,
+    //   when the inclusion delay is sampled
+    inclusion_proposer_rewards: Vec<u64>,
+}
 
 pub fn process_epoch(
-    pre_state: State,
+    state: &mut State,
     state_totals: &mut StateTotals,
     epoch_id: i32,
     output: &mut Output,
-) -> State {
+) {
     let mut epoch_report_row = EpochReportRow::open(epoch_id);
 
-    let mut post_state_validators = vec![];
-    let proposer_bitmap = pre_state.pick_epoch_proposers();
+    // SPEC: process_justification_and_finalization
+    state.current_epoch = epoch_id as u64;
+    process_justification_and_finalization(state, state_totals);
+    state.update_validator_churn_limit(state_totals.active_validators);
+
+    // deposits included in the blocks of this epoch
+    process_deposits(state, &mut epoch_report_row);
+
+    let proposer_indices = state.pick_epoch_proposers();
+    let proposer_bitmap = state.get_proposer_bitmap(&proposer_indices);
+    let proposer_availability = sample_proposer_availability(&state.config, &mut state.rng);
+
+    // slashings included in the blocks of this epoch
+    process_slashing_operations(state, &proposer_indices, &mut epoch_report_row);
+
+    // voluntary exits included in the blocks of this epoch
+    process_voluntary_exits(state, output.rows.last(), &mut epoch_report_row);
+
+    // sync aggregates included in the blocks of this epoch
+    process_sync_aggregates(
+        state,
+        state_totals,
+        &proposer_indices,
+        &proposer_availability,
+        &mut epoch_report_row,
+    );
+
+    // execution payloads of the blocks of this epoch
+    process_execution_rewards(state, &proposer_availability, &mut epoch_report_row);
+
+    let adjusted_total_slashing_balance =
+        get_adjusted_total_slashing_balance(state, state_totals.active_balance);
+
+    // each chunk of validators draws from its own random number generator,
+    //   seeded from the state one, so runs are reproducible whatever the
+    //   number of threads
+    let chunk_seeds: Vec<u64> = (0..state.validators.len().div_ceil(VALIDATORS_PER_CHUNK))
+        .map(|_| state.rng.gen())
+        .collect();
+
+    // validators are updated in place. They are taken out of the state
+    //   meanwhile, so the rest of it can be read by every thread.
+    let mut validators = std::mem::take(&mut state.validators);
+
+    let chunk_results: Vec<ChunkResult> = validators
+        .par_chunks_mut(VALIDATORS_PER_CHUNK)
+        .zip(chunk_seeds)
+        .enumerate()
+        .map(|(chunk_index, (chunk, chunk_seed))| {
+            process_validators_chunk(
+                state,
+                state_totals,
+                chunk,
+                chunk_index * VALIDATORS_PER_CHUNK,
+                &mut StdRng::seed_from_u64(chunk_seed),
+                &proposer_bitmap,
+                &proposer_availability,
+                adjusted_total_slashing_balance,
+            )
+        })
+        .collect();
+
+    state.validators = validators;
+
+    // reduction of the chunks
+    let mut post_state_totals = StateTotals::empty();
+    let mut inclusion_proposer_rewards = vec![0; proposer_indices.len()];
+    for chunk_result in chunk_results {
+        epoch_report_row.aggregate(&chunk_result.deltas);
+        post_state_totals.merge(&chunk_result.totals);
+
+        for (slot, proposer_reward) in chunk_result.inclusion_proposer_rewards.iter().enumerate() {
+            inclusion_proposer_rewards[slot] += proposer_reward;
+        }
+    }
+
+    // SPEC: process_rewards_and_penalties, proposer micro-rewards
+    //   the effective balance of the proposers catches up in the next epoch
+    let mut is_min_balance_stale = false;
+    for (slot, proposer_reward) in inclusion_proposer_rewards.iter().enumerate() {
+        let proposer = &mut state.validators[proposer_indices[slot]];
+        is_min_balance_stale |=
+            *proposer_reward > 0 && proposer.balance == post_state_totals.min_balance;
+
+        proposer.balance += proposer_reward;
+        post_state_totals.staked_balance += proposer_reward;
+        post_state_totals.max_balance =
+            std::cmp::max(post_state_totals.max_balance, proposer.balance);
+        epoch_report_row.deltas_proposer_rewards += proposer_reward;
+    }
+
+    // a proposer may have been the poorest validator, rare enough for a scan
+    if is_min_balance_stale {
+        post_state_totals.min_balance = state.get_min_balance();
+    }
+
+    // SPEC: process_registry_updates
+    process_registry_updates(state, &mut post_state_totals, &mut epoch_report_row);
+
+    // SPEC: process_final_updates reset slashings
+    let next_epoch = state.current_epoch + 1;
+    let epochs_per_slashings_vector = state.config.spec.epochs_per_slashings_vector;
+    state.slashings[(next_epoch % epochs_per_slashings_vector) as usize] = 0;
+
+    post_state_totals.finalize(&state.config);
+    *state_totals = post_state_totals;
 
-    for (validator_index, pre_state_validator) in pre_state.validators.iter().enumerate() {
+    epoch_report_row.close(state, state_totals);
+    output.push(epoch_report_row);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_validators_chunk(
+    state: &State,
+    state_totals: &StateTotals,
+    chunk: &mut [Validator],
+    first_validator_index: usize,
+    rng: &mut StdRng,
+    proposer_bitmap: &[usize],
+    proposer_availability: &[bool],
+    adjusted_total_slashing_balance: u64,
+) -> ChunkResult {
+    let mut chunk_result = ChunkResult {
+        deltas: Deltas::new(),
+        totals: StateTotals::empty(),
+        inclusion_proposer_rewards: vec![0; proposer_availability.len()],
+    };
+
+    for (offset, validator) in chunk.iter_mut().enumerate() {
         // SPEC: process_rewards_and_penalties.get_attestation_deltas()
         let mut deltas = Deltas::new();
-        let validator = pre_state_validator.update_previous_epoch_activity(
-            &pre_state,
-            &proposer_bitmap,
-            validator_index,
+        validator.update_previous_epoch_activity(
+            &state.config,
+            rng,
+            proposer_bitmap,
+            first_validator_index + offset,
+        );
+
+        let inclusion = sample_inclusion(
+            validator,
+            state.get_previous_epoch(),
+            &state.config,
+            rng,
+            proposer_availability,
         );
-        let base_reward = validator.get_base_reward(state_totals.sqrt_active_balance);
+
+        // SPEC: process_inactivity_updates
+        process_inactivity_updates(validator, state, inclusion.as_ref());
+
+        let base_reward = get_base_reward(validator, state, state_totals);
+
+        if let Some(inclusion) = &inclusion {
+            chunk_result.inclusion_proposer_rewards[inclusion.proposer_slot] +=
+                get_inclusion_proposer_reward(validator, base_reward, inclusion, &state.config);
+        }
 
         get_attestation_deltas(
-            &validator,
+            validator,
+            base_reward,
+            state,
+            state_totals,
+            inclusion.as_ref(),
+            &mut deltas,
+        );
+
+        // SPEC: process_rewards_and_penalties.get_inactivity_penalty_deltas()
+        get_inactivity_penalty_deltas(
+            validator,
             base_reward,
-            &pre_state,
-            &state_totals,
+            state,
+            inclusion.as_ref(),
+            &mut deltas,
+        );
+
+        // SPEC: process_slashings
+        process_slashings(
+            validator,
+            state.current_epoch,
+            state_totals.active_balance,
+            adjusted_total_slashing_balance,
+            &state.config.spec,
             &mut deltas,
         );
 
         // SPEC: process_rewards_and_penalties second half
-        let mut new_validator = apply_deltas(&validator, &deltas);
+        apply_deltas(validator, &deltas);
 
         // SPEC: process_final_updates update balances with hysteriesis
-        new_validator.update_effective_balance();
+        validator.update_effective_balance(&state.config.spec);
 
-        post_state_validators.push(new_validator);
-        epoch_report_row.aggregate(&deltas);
+        chunk_result.deltas.add(&deltas);
+        chunk_result.totals.add_validator(validator);
     }
 
-    let post_state = State {
-        config: pre_state.config,
-        validators: post_state_validators,
-    };
+    chunk_result
+}
 
-    epoch_report_row.close(&post_state, state_totals);
-    output.push(epoch_report_row);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    post_state
+    fn run_epochs_with_threads(threads: usize) -> Vec<u64> {
+        let mut state = State::from_config(Config::default());
+        state.config.probability_online = 0.9;
+        state.config.inclusion_delay = InclusionDelay::Sampled;
+        state.rng = StdRng::seed_from_u64(1);
+        let mut state_totals = StateTotals::new(&state);
+        let mut output = Output::new();
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        pool.install(|| {
+            for epoch_id in 0..3 {
+                process_epoch(&mut state, &mut state_totals, epoch_id, &mut output);
+            }
+        });
+
+        state.validators.iter().map(|v| v.balance).collect()
+    }
+
+    #[test]
+    fn same_outcome_whatever_the_threads() {
+        assert_eq!(run_epochs_with_threads(1), run_epochs_with_threads(4));
+    }
 }
@@ -0,0 +1,294 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Computes the expected outcome of the state transition, with no randomness
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use super::process_cohorts::*;
+use super::process_execution_rewards::*;
+use super::process_justification_and_finalization::*;
+use crate::types::*;
+
+pub fn process_epoch_analytic(
+    state: &mut State,
+    state_totals: &mut StateTotals,
+    epoch_id: i32,
+    output: &mut Output,
+) {
+    let mut epoch_report_row = EpochReportRow::open(epoch_id);
+
+    // SPEC: process_justification_and_finalization
+    //   the attesting balances are already the expected ones
+    state.current_epoch = epoch_id as u64;
+    process_justification_and_finalization(state, state_totals);
+    state.update_validator_churn_limit(state_totals.active_validators);
+
+    // all the validators share the same balances, in a single cohort, until
+    //   it is split by the exit queue
+    let mut cohorts = std::mem::take(&mut state.cohorts);
+    let mut epoch_deltas = Deltas::new();
+    for cohort in cohorts.iter_mut() {
+        epoch_deltas.add(&process_expected_cohort(state, state_totals, cohort));
+    }
+
+    epoch_report_row.aggregate(&epoch_deltas);
+    epoch_report_row.execution_rewards = get_expected_execution_rewards(&state.config);
+    state.cohorts = cohorts;
+
+    // SPEC: process_registry_updates
+    process_cohorts_registry_updates(state, &mut epoch_report_row);
+
+    // SPEC: process_final_updates reset slashings
+    let next_epoch = state.current_epoch + 1;
+    let epochs_per_slashings_vector = state.config.spec.epochs_per_slashings_vector;
+    state.slashings[(next_epoch % epochs_per_slashings_vector) as usize] = 0;
+
+    *state_totals = StateTotals::new(state);
+
+    epoch_report_row.close(state, state_totals);
+    output.push(epoch_report_row);
+}
+
+// moves the cohort to the expected outcome of the epoch, and returns the
+//   expected deltas of all of its validators
+fn process_expected_cohort(
+    state: &State,
+    state_totals: &StateTotals,
+    cohort: &mut Cohort,
+) -> Deltas {
+    // the deltas of each possible outcome of the epoch for a validator,
+    //   weighted by the expected number of validators getting it. The
+    //   expected score is split between the two integer scores around it.
+    let fraction = cohort.inactivity_score_fraction;
+    let mut epoch_deltas = Deltas::new();
+    let mut inactivity_score = 0.0;
+    for (score_offset, score_probability) in [(0, 1.0 - fraction), (1, fraction)].iter() {
+        if *score_probability == 0.0 {
+            continue;
+        }
+
+        let scored_cohort = Cohort {
+            inactivity_score: cohort.inactivity_score + score_offset,
+            ..cohort.clone()
+        };
+        for (probability, votes) in
+            get_votes_probabilities(&state.config, state_totals.active_validators)
+        {
+            let (validator, deltas) =
+                process_cohort_validator(state, state_totals, &scored_cohort, &votes);
+            let probability = score_probability * probability;
+            epoch_deltas.add_scaled(&deltas, probability * cohort.count as f64);
+            inactivity_score += probability * validator.inactivity_score as f64;
+        }
+    }
+
+    // SPEC: process_inactivity_updates
+    //   the cohort keeps the expected score, increases of less than 1 per
+    //   epoch included
+    cohort.inactivity_score = inactivity_score.floor() as u64;
+    cohort.inactivity_score_fraction = inactivity_score - inactivity_score.floor();
+
+    // SPEC: process_rewards_and_penalties second half
+    cohort.balance_sum = (cohort.balance_sum
+        + epoch_deltas.source_reward
+        + epoch_deltas.target_reward
+        + epoch_deltas.head_reward
+        + epoch_deltas.proposer_reward
+        + epoch_deltas.attester_reward
+        + epoch_deltas.sync_reward)
+        .saturating_sub(
+            epoch_deltas.source_penalty
+                + epoch_deltas.target_penalty
+                + epoch_deltas.head_penalty
+                + epoch_deltas.inactivity_penalty
+                + epoch_deltas.sync_penalty,
+        );
+
+    // SPEC: process_final_updates update balances with hysteriesis
+    let mut validator = Validator::new(0, &state.config.spec);
+    validator.balance = cohort.get_balance();
+    validator.effective_balance = cohort.effective_balance;
+    validator.update_effective_balance(&state.config.spec);
+    cohort.effective_balance = validator.effective_balance;
+
+    epoch_deltas
+}
+
+// probability of each duty and votes of a validator in the previous epoch
+fn get_votes_probabilities(config: &Config, validators: u64) -> Vec<(f64, Votes)> {
+    let probability_proposer = (config.spec.slots_per_epoch as f64 / validators as f64).min(1.0);
+    let probability_source = config.get_probability_source() as f64;
+    let probability_target = config.get_probability_target() as f64;
+    let probability_head = config.get_probability_head() as f64;
+
+    let mut votes_probabilities = vec![];
+
+    for (is_proposer, probability) in [
+        (true, probability_proposer),
+        (false, 1.0 - probability_proposer),
+    ]
+    .iter()
+    {
+        let outcomes = [
+            (1.0 - probability_source, false, false, false),
+            (probability_source - probability_target, true, false, false),
+            (probability_target - probability_head, true, true, false),
+            (probability_head, true, true, true),
+        ];
+
+        for (outcome_probability, source, target, head) in outcomes.iter() {
+            votes_probabilities.push((
+                probability * outcome_probability,
+                Votes {
+                    is_proposer: *is_proposer,
+                    has_matched_source: *source,
+                    has_matched_target: *target,
+                    has_matched_head: *head,
+                },
+            ));
+        }
+    }
+
+    votes_probabilities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_analytic_state(probability_online: f32) -> State {
+        let config = Config {
+            engine: Engine::Analytic,
+            probability_online,
+            ..Config::default()
+        };
+
+        State::from_config(config.build().unwrap())
+    }
+
+    fn run_epochs(mut state: State, epochs: i32) -> Output {
+        let mut state_totals = StateTotals::new(&state);
+        let mut output = Output::new();
+
+        for epoch_id in 0..epochs {
+            if state.config.engine == Engine::Analytic {
+                process_epoch_analytic(&mut state, &mut state_totals, epoch_id, &mut output);
+            } else {
+                super::super::process_epoch(&mut state, &mut state_totals, epoch_id, &mut output);
+            }
+        }
+
+        output
+    }
+
+    #[test]
+    fn get_votes_probabilities_sum() {
+        let state = get_analytic_state(0.9);
+        let probabilities = get_votes_probabilities(&state.config, 15_625);
+
+        let total: f64 = probabilities.iter().map(|(p, _)| p).sum();
+        let proposers: f64 = probabilities
+            .iter()
+            .filter(|(_, votes)| votes.is_proposer)
+            .map(|(p, _)| p)
+            .sum();
+
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!((proposers - 32.0 / 15_625.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_randomness() {
+        let mut state = get_analytic_state(0.9);
+        state.config.seed = 1;
+        let first_output = run_epochs(State::from_config(state.config.clone()), 5);
+        state.config.seed = 2;
+        let second_output = run_epochs(State::from_config(state.config.clone()), 5);
+
+        // every column but the time, the last one
+        let columns = EPOCH_REPORT_HEADER.len() - 1;
+        for (first_row, second_row) in first_output.rows.iter().zip(second_output.rows.iter()) {
+            assert_eq!(
+                first_row.get_values()[..columns],
+                second_row.get_values()[..columns]
+            );
+        }
+    }
+
+    #[test]
+    fn expectation_of_the_validators_engine() {
+        let analytic_output = run_epochs(get_analytic_state(0.9), 1);
+
+        let mut config = get_analytic_state(0.9).config;
+        config.engine = Engine::Validators;
+        config.seed = 1;
+        let validators_output = run_epochs(State::from_config(config), 1);
+
+        // 15,625 validators keep the sampling error within a few standard deviations
+        let analytic_row = &analytic_output.rows[0];
+        let validators_row = &validators_output.rows[0];
+        let expected = analytic_row.get_vote_rewards() as f64;
+        let sampled = validators_row.get_vote_rewards() as f64;
+        assert!((expected - sampled).abs() / expected < 0.02);
+
+        let expected = analytic_row.get_vote_penalties() as f64;
+        let sampled = validators_row.get_vote_penalties() as f64;
+        assert!((expected - sampled).abs() / expected < 0.1);
+
+        assert_eq!(
+            validators_row.total_validators,
+            analytic_row.total_validators
+        );
+    }
+
+    #[test]
+    fn altair_inactivity_score_below_one_per_epoch() {
+        let mut config = get_analytic_state(0.9).config;
+        config.fork = Fork::Altair;
+        let mut state = State::from_config(config);
+        let state_totals = StateTotals::new(&state);
+        let mut cohort = state.cohorts[0].clone();
+
+        // in the inactivity leak, the 10% missing the target add 4 to the
+        //   score, so the expected score first grows by 0.4 per epoch, less
+        //   once the 90% matching it take 1 off
+        state.current_epoch = 10;
+        assert!(state.is_in_inactivity_leak());
+
+        let mut inactivity_penalties = vec![];
+        for _ in 0..5 {
+            let deltas = process_expected_cohort(&state, &state_totals, &mut cohort);
+            inactivity_penalties.push(deltas.inactivity_penalty);
+        }
+
+        assert_eq!(0, cohort.inactivity_score);
+        assert!(cohort.inactivity_score_fraction > 0.4);
+        // the validators missing the target are penalized from the first
+        //   epoch, and the ones keeping some score on top of them since
+        assert!(inactivity_penalties[0] > 0);
+        assert!(inactivity_penalties.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn altair_sync_committee() {
+        let mut config = get_analytic_state(0.9).config;
+        config.fork = Fork::Altair;
+        let analytic_output = run_epochs(State::from_config(config.clone()), 1);
+
+        config.engine = Engine::Validators;
+        config.seed = 1;
+        let validators_output = run_epochs(State::from_config(config), 1);
+
+        // the expected rewards of the committee the validators engine picks
+        let analytic_row = &analytic_output.rows[0];
+        let validators_row = &validators_output.rows[0];
+        let expected = analytic_row.deltas_sync_rewards as f64;
+        let sampled = validators_row.deltas_sync_rewards as f64;
+        assert!(expected > 0.0);
+        assert!((expected - sampled).abs() / expected < 0.05);
+
+        let expected = analytic_row.deltas_sync_penalties as f64;
+        let sampled = validators_row.deltas_sync_penalties as f64;
+        assert!((expected - sampled).abs() / expected < 0.2);
+    }
+}
@@ -0,0 +1,440 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates the state transition with validators grouped in cohorts
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use super::apply_deltas::*;
+use super::get_attestation_deltas::*;
+use super::get_inactivity_penalty_deltas::*;
+use super::process_execution_rewards::*;
+use super::process_inactivity_updates::*;
+use super::process_justification_and_finalization::*;
+use super::process_registry_updates::queue_validator_exits;
+use super::process_sync_aggregate::get_expected_sync_deltas;
+use super::sample_inclusion_delay::sample_proposer_availability;
+use crate::types::*;
+use rand::distributions::{Binomial, WeightedIndex};
+use rand::prelude::*;
+use std::collections::BTreeMap;
+
+// duties and votes of a validator in the previous epoch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Votes {
+    pub is_proposer: bool,
+    pub has_matched_source: bool,
+    pub has_matched_target: bool,
+    pub has_matched_head: bool,
+}
+
+// validators of a cohort which got the same duties and votes in the previous epoch
+#[derive(Debug, PartialEq)]
+struct VoteClass {
+    count: u64,
+    votes: Votes,
+}
+
+pub fn process_epoch_cohorts(
+    state: &mut State,
+    state_totals: &mut StateTotals,
+    epoch_id: i32,
+    output: &mut Output,
+) {
+    let mut epoch_report_row = EpochReportRow::open(epoch_id);
+
+    // SPEC: process_justification_and_finalization
+    state.current_epoch = epoch_id as u64;
+    process_justification_and_finalization(state, state_totals);
+    state.update_validator_churn_limit(state_totals.active_validators);
+
+    let proposers_per_cohort = pick_cohorts_proposers(state);
+
+    // execution payloads of the blocks of this epoch, the proposers being
+    //   online with the online probability
+    if state.config.execution_rewards != ExecutionRewards::None {
+        let proposer_availability = sample_proposer_availability(&state.config, &mut state.rng);
+        process_execution_rewards(state, &proposer_availability, &mut epoch_report_row);
+    }
+
+    let cohorts = std::mem::take(&mut state.cohorts);
+
+    // cohorts with the same effective balance, inactivity score and exit
+    //   epoch, and a close enough balance are merged together, ordered so
+    //   runs are reproducible
+    let mut next_cohorts: BTreeMap<(u64, u64, u64, u64), Cohort> = BTreeMap::new();
+    let mut epoch_deltas = Deltas::new();
+
+    for (cohort, proposers) in cohorts.iter().zip(proposers_per_cohort) {
+        let mut balance_remainder = cohort.balance_sum % cohort.count;
+
+        for vote_class in split_cohort(cohort.count, proposers, &state.config, &mut state.rng) {
+            let (validator, deltas) =
+                process_cohort_validator(state, state_totals, cohort, &vote_class.votes);
+            epoch_deltas.add_times(&deltas, vote_class.count);
+
+            // the remainder of the balances is kept by the first class, so
+            //   the total staked balance stays exact
+            let key = (
+                validator.effective_balance,
+                validator.inactivity_score,
+                validator.balance / state.config.cohort_bucket,
+                cohort.exit_epoch,
+            );
+            let next_cohort = next_cohorts.entry(key).or_insert(Cohort {
+                count: 0,
+                balance_sum: 0,
+                effective_balance: validator.effective_balance,
+                inactivity_score: validator.inactivity_score,
+                inactivity_score_fraction: 0.0,
+                exit_epoch: cohort.exit_epoch,
+            });
+            next_cohort.count += vote_class.count;
+            next_cohort.balance_sum += validator.balance * vote_class.count + balance_remainder;
+            balance_remainder = 0;
+        }
+    }
+
+    epoch_report_row.aggregate(&epoch_deltas);
+    state.cohorts = next_cohorts.into_values().collect();
+
+    // SPEC: process_registry_updates
+    process_cohorts_registry_updates(state, &mut epoch_report_row);
+
+    // SPEC: process_final_updates reset slashings
+    let next_epoch = state.current_epoch + 1;
+    let epochs_per_slashings_vector = state.config.spec.epochs_per_slashings_vector;
+    state.slashings[(next_epoch % epochs_per_slashings_vector) as usize] = 0;
+
+    // there are only a few cohorts, the totals are computed from scratch
+    *state_totals = StateTotals::new(state);
+
+    epoch_report_row.close(state, state_totals);
+    output.push(epoch_report_row);
+}
+
+// every validator of a cohort with the same votes goes through the same
+//   state transition, that of a validator with the mean balance
+pub fn process_cohort_validator(
+    state: &State,
+    state_totals: &StateTotals,
+    cohort: &Cohort,
+    votes: &Votes,
+) -> (Validator, Deltas) {
+    let mut validator = Validator {
+        balance: cohort.get_balance(),
+        effective_balance: cohort.effective_balance,
+        is_active: true,
+        is_slashed: false,
+        has_matched_source: votes.has_matched_source,
+        has_matched_target: votes.has_matched_target,
+        has_matched_head: votes.has_matched_head,
+        has_equivocated: false,
+        is_proposer: votes.is_proposer,
+        activation_eligibility_epoch: 0,
+        activation_epoch: 0,
+        exit_epoch: config::FAR_FUTURE_EPOCH,
+        withdrawable_epoch: config::FAR_FUTURE_EPOCH,
+        inactivity_score: cohort.inactivity_score,
+    };
+
+    // SPEC: process_inactivity_updates
+    //   with the expected inclusion delay
+    process_inactivity_updates(&mut validator, state, None);
+
+    // SPEC: process_rewards_and_penalties
+    let mut deltas = Deltas::new();
+    let base_reward = get_base_reward(&validator, state, state_totals);
+    get_attestation_deltas(
+        &validator,
+        base_reward,
+        state,
+        state_totals,
+        None,
+        &mut deltas,
+    );
+    get_inactivity_penalty_deltas(&validator, base_reward, state, None, &mut deltas);
+
+    // SPEC: process_sync_aggregate
+    //   the expected rewards of the blocks of the epoch
+    get_expected_sync_deltas(&validator, state, state_totals, &mut deltas);
+    apply_deltas(&mut validator, &deltas);
+
+    // SPEC: process_final_updates update balances with hysteriesis
+    validator.update_effective_balance(&state.config.spec);
+
+    (validator, deltas)
+}
+
+// the cohorts at the ejection balance are split by the exit epochs the exit
+//   queue gives to their validators, and the cohorts past their exit epoch
+//   stop being processed
+pub fn process_cohorts_registry_updates(state: &mut State, epoch_report_row: &mut EpochReportRow) {
+    let mut cohorts = vec![];
+
+    for index in 0..state.cohorts.len() {
+        let cohort = state.cohorts[index].clone();
+        if cohort.exit_epoch != config::FAR_FUTURE_EPOCH
+            || cohort.effective_balance > config::EJECTION_BALANCE
+        {
+            cohorts.push(cohort);
+            continue;
+        }
+
+        let balance = cohort.get_balance();
+        let mut balance_remainder = cohort.balance_sum % cohort.count;
+        for (exit_epoch, count) in queue_validator_exits(state, cohort.count) {
+            cohorts.push(Cohort {
+                count,
+                balance_sum: balance * count + balance_remainder,
+                exit_epoch,
+                ..cohort.clone()
+            });
+            balance_remainder = 0;
+        }
+    }
+
+    let current_epoch = state.current_epoch;
+    let (exited_cohorts, cohorts): (Vec<Cohort>, Vec<Cohort>) = cohorts
+        .into_iter()
+        .partition(|c| c.exit_epoch <= current_epoch);
+
+    epoch_report_row.exited_validators += exited_cohorts.iter().map(|c| c.count).sum::<u64>();
+    state.exited_cohorts.extend(exited_cohorts);
+    state.cohorts = cohorts;
+}
+
+// number of proposers of the epoch in each cohort, picked with a probability
+//   proportional to the effective balance
+fn pick_cohorts_proposers(state: &mut State) -> Vec<u64> {
+    let mut proposers = vec![0; state.cohorts.len()];
+    let weights: Vec<u64> = state
+        .cohorts
+        .iter()
+        .map(|c| c.count * c.effective_balance)
+        .collect();
+
+    let distribution = match WeightedIndex::new(&weights) {
+        Ok(distribution) => distribution,
+        Err(_) => return proposers,
+    };
+
+    for _ in 0..state.config.spec.slots_per_epoch {
+        let index = distribution.sample(&mut state.rng);
+        proposers[index] = std::cmp::min(proposers[index] + 1, state.cohorts[index].count);
+    }
+
+    proposers
+}
+
+// splits the validators of a cohort by their duties and votes. The votes
+//   are nested, so each split is a binomial draw on the previous one.
+fn split_cohort(count: u64, proposers: u64, config: &Config, rng: &mut StdRng) -> Vec<VoteClass> {
+    let probability_source = config.get_probability_source() as f64;
+    let probability_target =
+        get_conditional_probability(config.get_probability_target() as f64, probability_source);
+    let probability_head = get_conditional_probability(
+        config.get_probability_head() as f64,
+        config.get_probability_target() as f64,
+    );
+
+    let mut vote_classes = vec![];
+
+    for (is_proposer, validators) in [(true, proposers), (false, count - proposers)].iter() {
+        let matched_source = Binomial::new(*validators, probability_source).sample(rng);
+        let matched_target = Binomial::new(matched_source, probability_target).sample(rng);
+        let matched_head = Binomial::new(matched_target, probability_head).sample(rng);
+
+        let splits = [
+            (validators - matched_source, false, false, false),
+            (matched_source - matched_target, true, false, false),
+            (matched_target - matched_head, true, true, false),
+            (matched_head, true, true, true),
+        ];
+
+        for (count, source, target, head) in splits.iter() {
+            if *count > 0 {
+                vote_classes.push(VoteClass {
+                    count: *count,
+                    votes: Votes {
+                        is_proposer: *is_proposer,
+                        has_matched_source: *source,
+                        has_matched_target: *target,
+                        has_matched_head: *head,
+                    },
+                });
+            }
+        }
+    }
+
+    vote_classes
+}
+
+fn get_conditional_probability(probability: f64, given_probability: f64) -> f64 {
+    if given_probability > 0.0 {
+        (probability / given_probability).min(1.0)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_cohorts_state() -> State {
+        let config = Config {
+            engine: Engine::Cohorts,
+            ..Config::default()
+        };
+
+        State::from_config(config)
+    }
+
+    #[test]
+    fn split_cohort_conserves_validators() {
+        let mut state = get_cohorts_state();
+        state.config.probability_online = 0.9;
+        state.config.probability_target = 0.8;
+        state.config.probability_head = 0.7;
+
+        let vote_classes = split_cohort(1_000_000, 3, &state.config, &mut state.rng);
+
+        let count: u64 = vote_classes.iter().map(|c| c.count).sum();
+        let proposers: u64 = vote_classes
+            .iter()
+            .filter(|c| c.votes.is_proposer)
+            .map(|c| c.count)
+            .sum();
+        let matched_head: u64 = vote_classes
+            .iter()
+            .filter(|c| c.votes.has_matched_head)
+            .map(|c| c.count)
+            .sum();
+
+        assert_eq!(1_000_000, count);
+        assert_eq!(3, proposers);
+
+        // about 0.9 * 0.8 * 0.7 of the validators
+        assert!((500_000..508_000).contains(&matched_head));
+    }
+
+    #[test]
+    fn split_cohort_perfect_participation() {
+        let mut state = get_cohorts_state();
+        state.config.probability_online = 1.0;
+
+        let vote_classes = split_cohort(100, 0, &state.config, &mut state.rng);
+
+        assert_eq!(
+            vec![VoteClass {
+                count: 100,
+                votes: Votes {
+                    is_proposer: false,
+                    has_matched_source: true,
+                    has_matched_target: true,
+                    has_matched_head: true,
+                },
+            }],
+            vote_classes
+        );
+    }
+
+    #[test]
+    fn same_attestation_deltas_as_the_validators_engine() {
+        // with a perfect participation, the only randomness left is the pick
+        //   of the proposers, which does not change the attestation deltas
+        let mut validators_state = State::from_config(Config::default());
+        validators_state.config.probability_online = 1.0;
+        let mut cohorts_state = get_cohorts_state();
+        cohorts_state.config.probability_online = 1.0;
+
+        let mut validators_totals = StateTotals::new(&validators_state);
+        let mut cohorts_totals = StateTotals::new(&cohorts_state);
+        let mut validators_output = Output::new();
+        let mut cohorts_output = Output::new();
+
+        for epoch_id in 0..3 {
+            super::super::process_epoch(
+                &mut validators_state,
+                &mut validators_totals,
+                epoch_id,
+                &mut validators_output,
+            );
+            process_epoch_cohorts(
+                &mut cohorts_state,
+                &mut cohorts_totals,
+                epoch_id,
+                &mut cohorts_output,
+            );
+        }
+
+        for (validators_row, cohorts_row) in validators_output
+            .rows
+            .iter()
+            .zip(cohorts_output.rows.iter())
+        {
+            assert_eq!(
+                validators_row.get_vote_rewards(),
+                cohorts_row.get_vote_rewards()
+            );
+            assert_eq!(
+                validators_row.deltas_attester_rewards,
+                cohorts_row.deltas_attester_rewards
+            );
+            assert_eq!(
+                validators_row.total_validators,
+                cohorts_row.total_validators
+            );
+        }
+        assert_eq!(
+            validators_totals.active_validators,
+            cohorts_totals.active_validators
+        );
+    }
+
+    #[test]
+    fn ejected_cohorts_exit_through_the_queue() {
+        let mut state = get_cohorts_state();
+        let mut epoch_report_row = EpochReportRow::open(10);
+        state.current_epoch = 10;
+        state.cohorts.push(Cohort {
+            count: 9,
+            balance_sum: 9 * 16_500_000_000 + 5,
+            effective_balance: 16_000_000_000,
+            inactivity_score: 40,
+            inactivity_score_fraction: 0.0,
+            exit_epoch: config::FAR_FUTURE_EPOCH,
+        });
+
+        process_cohorts_registry_updates(&mut state, &mut epoch_report_row);
+
+        // the churn limit for 15,634 validators is 4
+        let exits: Vec<(u64, u64, u64)> = state.cohorts[1..]
+            .iter()
+            .map(|c| (c.count, c.balance_sum, c.exit_epoch))
+            .collect();
+        assert_eq!(
+            vec![
+                (4, 4 * 16_500_000_000 + 5, 15),
+                (4, 4 * 16_500_000_000, 16),
+                (1, 16_500_000_000, 17),
+            ],
+            exits
+        );
+        assert_eq!(config::FAR_FUTURE_EPOCH, state.cohorts[0].exit_epoch);
+
+        // the validators are active until their exit epoch
+        for epoch in 11..17 {
+            state.current_epoch = epoch;
+            process_cohorts_registry_updates(&mut state, &mut epoch_report_row);
+        }
+
+        assert_eq!(8, epoch_report_row.exited_validators);
+        assert_eq!(15626, state.get_total_active_validators());
+        assert_eq!(15634, state.get_total_validators());
+        assert_eq!(
+            15625 * 32_000_000_000 + 9 * 16_500_000_000 + 5,
+            state.get_total_staked_balance()
+        );
+    }
+}
@@ -0,0 +1,155 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates `process_deposit` block operations
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::types::*;
+
+// deposits of the epoch join the pending ones. the blocks of the epoch
+//   include up to MAX_DEPOSITS each, the rest wait for the next epoch.
+pub fn process_deposits(state: &mut State, epoch_report_row: &mut EpochReportRow) {
+    let current_epoch = state.current_epoch;
+
+    for _ in 0..state.config.deposits_per_epoch {
+        state.pending_deposits.push_back(Deposit {
+            epoch: current_epoch as i32,
+            amount: state.config.spec.max_effective_balance,
+            validator_index: None,
+        });
+    }
+
+    let scheduled: Vec<Deposit> = state
+        .config
+        .deposit_schedule
+        .iter()
+        .filter(|deposit| deposit.epoch as u64 == current_epoch)
+        .cloned()
+        .collect();
+    state.pending_deposits.extend(scheduled);
+
+    for _ in 0..config::MAX_DEPOSITS * state.config.spec.slots_per_epoch {
+        let deposit = match state.pending_deposits.pop_front() {
+            Some(deposit) => deposit,
+            None => break,
+        };
+
+        process_deposit(state, &deposit);
+
+        epoch_report_row.deposits += 1;
+        epoch_report_row.deposited_balance += deposit.amount;
+    }
+}
+
+pub fn process_deposit(state: &mut State, deposit: &Deposit) {
+    // SPEC
+    /*
+        pubkey = deposit.data.pubkey
+        amount = deposit.data.amount
+        validator_pubkeys = [v.pubkey for v in state.validators]
+        if pubkey not in validator_pubkeys:
+            # Verify the deposit signature (proof of possession) which is not checked by the deposit contract
+            ...
+            # Add validator and balance entries
+            state.validators.append(get_validator_from_deposit(state, deposit))
+            state.balances.append(amount)
+        else:
+            # Increase balance by deposit amount
+            index = ValidatorIndex(validator_pubkeys.index(pubkey))
+            increase_balance(state, index, amount)
+    */
+
+    match deposit.validator_index {
+        Some(index) => {
+            state.validators[index].balance += deposit.amount;
+        }
+        None => {
+            let validator = Validator::new(deposit.amount, &state.config.spec);
+            state.validators.push(validator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top_up(epoch: i32, eth: u64, validator_index: usize) -> Deposit {
+        Deposit {
+            epoch,
+            amount: eth * 1_000_000_000,
+            validator_index: Some(validator_index),
+        }
+    }
+
+    #[test]
+    fn new_validators_per_epoch() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.config.deposits_per_epoch = 10;
+
+        process_deposits(&mut state, &mut epoch_report_row);
+
+        assert_eq!(15635, state.validators.len());
+        assert_eq!(10, epoch_report_row.deposits);
+        assert_eq!(320_000_000_000, epoch_report_row.deposited_balance);
+
+        let new_validator = &state.validators[15634];
+        assert!(!new_validator.is_active);
+        assert!(new_validator.is_eligible_for_activation_queue(&state.config.spec));
+    }
+
+    #[test]
+    fn deposits_per_block_limit() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.config.deposits_per_epoch = 600;
+
+        process_deposits(&mut state, &mut epoch_report_row);
+        assert_eq!(512, epoch_report_row.deposits);
+        assert_eq!(88, state.pending_deposits.len());
+
+        state.config.deposits_per_epoch = 0;
+        process_deposits(&mut state, &mut epoch_report_row);
+        assert_eq!(600, epoch_report_row.deposits);
+        assert_eq!(16225, state.validators.len());
+    }
+
+    #[test]
+    fn top_ups() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.config.deposit_schedule = vec![top_up(0, 2, 7), top_up(1, 3, 8)];
+
+        process_deposits(&mut state, &mut epoch_report_row);
+
+        assert_eq!(15625, state.validators.len());
+        assert_eq!(34_000_000_000, state.validators[7].balance);
+        assert_eq!(32_000_000_000, state.validators[8].balance);
+    }
+
+    #[test]
+    fn partial_deposit_top_up() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.config.deposit_schedule = vec![
+            Deposit {
+                epoch: 0,
+                amount: 16_000_000_000,
+                validator_index: None,
+            },
+            top_up(0, 16, 15625),
+        ];
+
+        process_deposits(&mut state, &mut epoch_report_row);
+
+        // the effective balance catches up at the end of the epoch
+        let spec = &state.config.spec;
+        let validator = &mut state.validators[15625];
+        assert_eq!(32_000_000_000, validator.balance);
+        assert!(!validator.is_eligible_for_activation_queue(spec));
+
+        validator.update_effective_balance(spec);
+        assert!(validator.is_eligible_for_activation_queue(spec));
+    }
+}
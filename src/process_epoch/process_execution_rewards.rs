@@ -0,0 +1,114 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates the execution layer income of the proposers, priority fees and
+//   MEV, paid to their fee recipients
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::types::*;
+use rand::distributions::LogNormal;
+use rand::prelude::*;
+
+// each block proposed in the epoch pays the fee recipient of its proposer.
+//   The beacon balances are left as they are.
+pub fn process_execution_rewards(
+    state: &mut State,
+    proposer_availability: &[bool],
+    epoch_report_row: &mut EpochReportRow,
+) {
+    if state.config.execution_rewards == ExecutionRewards::None {
+        return;
+    }
+
+    for is_available in proposer_availability {
+        if *is_available {
+            epoch_report_row.execution_rewards += get_block_execution_reward(state);
+        }
+    }
+}
+
+// no randomness: the expected blocks of the epoch, each of the mean value
+pub fn get_expected_execution_rewards(config: &Config) -> u64 {
+    let expected_blocks = config.spec.slots_per_epoch as f64 * config.probability_online as f64;
+
+    (expected_blocks * config.execution_rewards.get_mean()).round() as u64
+}
+
+fn get_block_execution_reward(state: &mut State) -> u64 {
+    match &state.config.execution_rewards {
+        ExecutionRewards::None => 0,
+        ExecutionRewards::Fixed(value) => *value,
+        ExecutionRewards::LogNormal(median, sigma) => LogNormal::new(median.ln(), *sigma)
+            .sample(&mut state.rng)
+            .round() as u64,
+        ExecutionRewards::Replay(values) => {
+            let value = values[state.execution_rewards_index % values.len()];
+            state.execution_rewards_index += 1;
+            value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_state(execution_rewards: ExecutionRewards) -> State {
+        State::from_config(Config {
+            execution_rewards,
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn fixed() {
+        let mut state = get_state(ExecutionRewards::Fixed(50_000_000));
+        let mut epoch_report_row = EpochReportRow::open(0);
+        let staked_balance = state.get_total_staked_balance();
+
+        // only the slots with a block
+        let mut proposer_availability = [true; 32];
+        proposer_availability[0] = false;
+        process_execution_rewards(&mut state, &proposer_availability, &mut epoch_report_row);
+
+        assert_eq!(31 * 50_000_000, epoch_report_row.execution_rewards);
+        assert_eq!(staked_balance, state.get_total_staked_balance());
+    }
+
+    #[test]
+    fn lognormal() {
+        let mut state = get_state(ExecutionRewards::LogNormal(40_000_000.0, 0.0));
+        let mut epoch_report_row = EpochReportRow::open(0);
+
+        // with no dispersion, every block is worth the median
+        process_execution_rewards(&mut state, &[true; 32], &mut epoch_report_row);
+        assert_eq!(32 * 40_000_000, epoch_report_row.execution_rewards);
+    }
+
+    #[test]
+    fn replay() {
+        let mut state = get_state(ExecutionRewards::Replay(vec![1, 10, 100]));
+
+        // the values are replayed in order across epochs, from the start once
+        //   they run out
+        let mut epoch_report_row = EpochReportRow::open(0);
+        process_execution_rewards(&mut state, &[true, false, true], &mut epoch_report_row);
+        assert_eq!(11, epoch_report_row.execution_rewards);
+
+        let mut epoch_report_row = EpochReportRow::open(1);
+        process_execution_rewards(&mut state, &[true, true, true], &mut epoch_report_row);
+        assert_eq!(111, epoch_report_row.execution_rewards);
+    }
+
+    #[test]
+    fn expected_execution_rewards() {
+        let config = Config {
+            execution_rewards: ExecutionRewards::Fixed(50_000_000),
+            probability_online: 0.5,
+            ..Config::default()
+        };
+
+        assert_eq!(16 * 50_000_000, get_expected_execution_rewards(&config));
+        assert_eq!(0, get_expected_execution_rewards(&Config::default()));
+    }
+}
@@ -0,0 +1,154 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates the Altair `process_inactivity_updates`
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use super::get_flag_index_deltas::ParticipationFlags;
+use super::sample_inclusion_delay::Inclusion;
+use crate::types::*;
+
+// the votes of the validator in the previous epoch, and their inclusion, are
+//   already sampled
+pub fn process_inactivity_updates(
+    validator: &mut Validator,
+    state: &State,
+    inclusion: Option<&Inclusion>,
+) {
+    // SPEC
+    /*
+        # Skip the genesis epoch as score updates are based on the previous epoch participation
+        if get_current_epoch(state) == GENESIS_EPOCH:
+            return
+
+        for index in get_eligible_validator_indices(state):
+            # Increase the inactivity score of inactive validators
+            if index in get_unslashed_participating_indices(state, TIMELY_TARGET_FLAG_INDEX, get_previous_epoch(state)):
+                state.inactivity_scores[index] -= min(1, state.inactivity_scores[index])
+            else:
+                state.inactivity_scores[index] += INACTIVITY_SCORE_BIAS
+            # Decrease the inactivity score of all eligible validators during a leak-free epoch
+            if not is_in_inactivity_leak(state):
+                state.inactivity_scores[index] -= min(INACTIVITY_SCORE_RECOVERY_RATE, state.inactivity_scores[index])
+    */
+
+    if state.config.fork != Fork::Altair
+        || state.current_epoch == 0
+        || !validator.is_eligible(state.get_previous_epoch())
+    {
+        return;
+    }
+
+    let spec = &state.config.spec;
+
+    // slashed validators never match the target
+    let flags = ParticipationFlags::new(validator, inclusion, &state.config);
+    if flags.timely_target {
+        validator.inactivity_score -= std::cmp::min(1, validator.inactivity_score);
+    } else {
+        validator.inactivity_score += spec.inactivity_score_bias;
+    }
+
+    if !state.is_in_inactivity_leak() {
+        validator.inactivity_score -= std::cmp::min(
+            spec.inactivity_score_recovery_rate,
+            validator.inactivity_score,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_altair_state() -> State {
+        State::from_config(Config {
+            fork: Fork::Altair,
+            ..Config::default()
+        })
+    }
+
+    fn get_active_validator(state: &State) -> Validator {
+        let mut validator = Validator::new(32_000_000_000, &state.config.spec);
+        validator.activation_epoch = 0;
+        validator.is_active = true;
+        validator
+    }
+
+    #[test]
+    fn inactivity_leak() {
+        let mut state = get_altair_state();
+        state.current_epoch = 10;
+        let mut validator = get_active_validator(&state);
+
+        // 4 more for each missed target, 1 less for each matched one
+        for _ in 0..5 {
+            process_inactivity_updates(&mut validator, &state, None);
+        }
+        assert_eq!(20, validator.inactivity_score);
+
+        validator.has_matched_target = true;
+        process_inactivity_updates(&mut validator, &state, None);
+        assert_eq!(19, validator.inactivity_score);
+    }
+
+    #[test]
+    fn recovery_without_leak() {
+        let mut state = get_altair_state();
+        state.current_epoch = 10;
+        state.finalized_epoch = 8;
+
+        let mut validator = get_active_validator(&state);
+        validator.inactivity_score = 40;
+
+        // 1 for the matched target, and the recovery rate
+        validator.has_matched_target = true;
+        process_inactivity_updates(&mut validator, &state, None);
+        assert_eq!(23, validator.inactivity_score);
+
+        // a missed target outside of the leak is forgiven at once
+        validator.has_matched_target = false;
+        validator.inactivity_score = 0;
+        process_inactivity_updates(&mut validator, &state, None);
+        assert_eq!(0, validator.inactivity_score);
+    }
+
+    #[test]
+    fn late_target() {
+        let mut state = get_altair_state();
+        state.config.inclusion_delay = InclusionDelay::Sampled;
+        state.current_epoch = 10;
+        let mut validator = get_active_validator(&state);
+        validator.has_matched_target = true;
+
+        // a matching target included after an epoch, or not at all, sets no flag
+        let late_inclusion = Inclusion {
+            delay: 33,
+            proposer_slot: 0,
+        };
+        process_inactivity_updates(&mut validator, &state, Some(&late_inclusion));
+        process_inactivity_updates(&mut validator, &state, None);
+        assert_eq!(8, validator.inactivity_score);
+
+        let timely_inclusion = Inclusion {
+            delay: 32,
+            proposer_slot: 0,
+        };
+        process_inactivity_updates(&mut validator, &state, Some(&timely_inclusion));
+        assert_eq!(7, validator.inactivity_score);
+    }
+
+    #[test]
+    fn genesis_and_phase0() {
+        let mut state = get_altair_state();
+        let mut validator = get_active_validator(&state);
+
+        process_inactivity_updates(&mut validator, &state, None);
+        assert_eq!(0, validator.inactivity_score);
+
+        state.current_epoch = 10;
+        state.config.fork = Fork::Phase0;
+        process_inactivity_updates(&mut validator, &state, None);
+        assert_eq!(0, validator.inactivity_score);
+    }
+}
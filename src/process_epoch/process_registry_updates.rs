@@ -0,0 +1,310 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates `process_registry_updates` ops during the state transition
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::types::*;
+
+pub fn process_registry_updates(
+    state: &mut State,
+    state_totals: &mut StateTotals,
+    epoch_report_row: &mut EpochReportRow,
+) {
+    // SPEC
+    /*
+        # Process activation eligibility and ejections
+        for index, validator in enumerate(state.validators):
+            if is_eligible_for_activation_queue(validator):
+                validator.activation_eligibility_epoch = get_current_epoch(state) + 1
+
+            if is_active_validator(validator, get_current_epoch(state)) and validator.effective_balance <= EJECTION_BALANCE:
+                initiate_validator_exit(state, ValidatorIndex(index))
+
+        # Queue validators eligible for activation and not yet dequeued for activation
+        activation_queue = sorted([
+            index for index, validator in enumerate(state.validators)
+            if is_eligible_for_activation(state, validator)
+            # Order by the sequence of activation_eligibility_epoch setting and then index
+        ], key=lambda index: (state.validators[index].activation_eligibility_epoch, index))
+        # Dequeued validators for activation up to churn limit
+        for index in activation_queue[:get_validator_churn_limit(state)]:
+            validator = state.validators[index]
+            validator.activation_epoch = compute_activation_exit_epoch(get_current_epoch(state))
+    */
+
+    let current_epoch = state.current_epoch;
+    let finalized_epoch = state.finalized_epoch;
+
+    for index in 0..state.validators.len() {
+        let validator = &mut state.validators[index];
+
+        if validator.is_eligible_for_activation_queue(&state.config.spec) {
+            validator.activation_eligibility_epoch = current_epoch + 1;
+        }
+
+        if validator.is_active_validator(current_epoch)
+            && validator.effective_balance <= config::EJECTION_BALANCE
+        {
+            initiate_validator_exit(state, index);
+        }
+    }
+
+    let mut activation_queue: Vec<usize> = state
+        .validators
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| v.is_eligible_for_activation(finalized_epoch))
+        .map(|(index, _)| index)
+        .collect();
+    activation_queue.sort_by_key(|index| {
+        (
+            state.validators[*index].activation_eligibility_epoch,
+            *index,
+        )
+    });
+
+    let churn_limit = state.get_validator_churn_limit() as usize;
+    for index in activation_queue.iter().take(churn_limit) {
+        state.validators[*index].activation_epoch = compute_activation_exit_epoch(current_epoch);
+    }
+
+    // refresh the activity flag for the next epoch processing
+    for validator in state.validators.iter_mut() {
+        let is_active = validator.is_active_validator(current_epoch);
+
+        if is_active && !validator.is_active {
+            epoch_report_row.activated_validators += 1;
+        }
+        if !is_active && validator.is_active {
+            epoch_report_row.exited_validators += 1;
+        }
+        if !is_active && validator.activation_epoch > current_epoch {
+            epoch_report_row.pending_validators += 1;
+        }
+
+        state_totals.update_activity(validator, is_active);
+        validator.is_active = is_active;
+    }
+}
+
+pub fn compute_activation_exit_epoch(epoch: u64) -> u64 {
+    epoch + 1 + config::MAX_SEED_LOOKAHEAD
+}
+
+pub fn initiate_validator_exit(state: &mut State, index: usize) {
+    // SPEC
+    /*
+        # Return if validator already initiated exit
+        validator = state.validators[index]
+        if validator.exit_epoch != FAR_FUTURE_EPOCH:
+            return
+
+        # Compute exit queue epoch
+        exit_epochs = [v.exit_epoch for v in state.validators if v.exit_epoch != FAR_FUTURE_EPOCH]
+        exit_queue_epoch = max(exit_epochs + [compute_activation_exit_epoch(get_current_epoch(state))])
+        exit_queue_churn = len([v for v in state.validators if v.exit_epoch == exit_queue_epoch])
+        if exit_queue_churn >= get_validator_churn_limit(state):
+            exit_queue_epoch += Epoch(1)
+
+        # Set validator exit epoch and withdrawable epoch
+        validator.exit_epoch = exit_queue_epoch
+        validator.withdrawable_epoch = Epoch(validator.exit_epoch + MIN_VALIDATOR_WITHDRAWABILITY_DELAY)
+    */
+
+    if state.validators[index].exit_epoch != config::FAR_FUTURE_EPOCH {
+        return;
+    }
+
+    let (exit_epoch, _) = queue_validator_exits(state, 1)[0];
+
+    let validator = &mut state.validators[index];
+    validator.exit_epoch = exit_epoch;
+    validator.withdrawable_epoch =
+        validator.exit_epoch + config::MIN_VALIDATOR_WITHDRAWABILITY_DELAY;
+}
+
+// assigns an exit epoch to `count` validators, as that many calls to
+//   `initiate_validator_exit` would, and returns how many exit at each epoch
+pub fn queue_validator_exits(state: &mut State, count: u64) -> Vec<(u64, u64)> {
+    // the exit queue epoch only moves forward, so we keep it on the state
+    //   instead of looking for the maximum exit epoch of the registry
+    let activation_exit_epoch = compute_activation_exit_epoch(state.current_epoch);
+    if state.exit_queue_epoch < activation_exit_epoch {
+        state.exit_queue_epoch = activation_exit_epoch;
+        state.exit_queue_churn = 0;
+    }
+
+    let churn_limit = state.get_validator_churn_limit();
+    let mut exits = vec![];
+    let mut remaining = count;
+    while remaining > 0 {
+        if state.exit_queue_churn >= churn_limit {
+            state.exit_queue_epoch += 1;
+            state.exit_queue_churn = 0;
+        }
+
+        let exiting = std::cmp::min(remaining, churn_limit - state.exit_queue_churn);
+        state.exit_queue_churn += exiting;
+        remaining -= exiting;
+        exits.push((state.exit_queue_epoch, exiting));
+    }
+
+    exits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_state_with_queue(queued_validators: usize) -> State {
+        let mut state = State::from_config(Config::default());
+        let spec = state.config.spec.clone();
+        for _ in 0..queued_validators {
+            state
+                .validators
+                .push(Validator::new(spec.max_effective_balance, &spec));
+        }
+
+        state
+    }
+
+    #[test]
+    fn activation_eligibility() {
+        let mut state = get_state_with_queue(1);
+        let mut epoch_report_row = EpochReportRow::open(0);
+        let spec = state.config.spec.clone();
+        state
+            .validators
+            .push(Validator::new(spec.max_effective_balance - 1, &spec));
+        state.current_epoch = 3;
+        let mut state_totals = StateTotals::new(&state);
+
+        process_registry_updates(&mut state, &mut state_totals, &mut epoch_report_row);
+
+        // only validators with a full effective balance are queued
+        assert_eq!(4, state.validators[15625].activation_eligibility_epoch);
+        assert_eq!(
+            config::FAR_FUTURE_EPOCH,
+            state.validators[15626].activation_eligibility_epoch
+        );
+
+        // eligibility is not finalized yet
+        assert_eq!(
+            config::FAR_FUTURE_EPOCH,
+            state.validators[15625].activation_epoch
+        );
+        assert_eq!(2, epoch_report_row.pending_validators);
+        assert_eq!(0, epoch_report_row.activated_validators);
+    }
+
+    #[test]
+    fn activation_queue_churn() {
+        let mut state = get_state_with_queue(10);
+        let mut state_totals = StateTotals::new(&state);
+        let mut epoch_report_row = EpochReportRow::open(0);
+
+        state.current_epoch = 3;
+        process_registry_updates(&mut state, &mut state_totals, &mut epoch_report_row);
+
+        state.current_epoch = 6;
+        state.finalized_epoch = 5;
+        process_registry_updates(&mut state, &mut state_totals, &mut epoch_report_row);
+
+        // the churn limit for 15,625 validators is 4
+        let activation_epochs: Vec<u64> = state.validators[15625..]
+            .iter()
+            .map(|v| v.activation_epoch)
+            .collect();
+        assert_eq!(vec![11; 4], activation_epochs[..4].to_vec());
+        assert_eq!(
+            vec![config::FAR_FUTURE_EPOCH; 6],
+            activation_epochs[4..].to_vec()
+        );
+
+        state.current_epoch = 7;
+        process_registry_updates(&mut state, &mut state_totals, &mut epoch_report_row);
+        assert_eq!(12, state.validators[15629].activation_epoch);
+
+        // validators are active at their activation epoch
+        let mut epoch_report_row = EpochReportRow::open(11);
+        state.current_epoch = 11;
+        process_registry_updates(&mut state, &mut state_totals, &mut epoch_report_row);
+
+        assert_eq!(4, epoch_report_row.activated_validators);
+        assert_eq!(6, epoch_report_row.pending_validators);
+        assert_eq!(15629, state.get_total_active_validators());
+
+        // the totals follow the activations
+        state_totals.finalize(&state.config);
+        assert_eq!(StateTotals::new(&state), state_totals);
+    }
+
+    #[test]
+    fn exit_queue_churn() {
+        let mut state = State::from_config(Config::default());
+        state.current_epoch = 10;
+
+        for index in 0..10 {
+            initiate_validator_exit(&mut state, index);
+        }
+
+        // a second exit does not change anything
+        initiate_validator_exit(&mut state, 0);
+
+        let exit_epochs: Vec<u64> = state.validators[..10]
+            .iter()
+            .map(|v| v.exit_epoch)
+            .collect();
+        assert_eq!(vec![15, 15, 15, 15, 16, 16, 16, 16, 17, 17], exit_epochs);
+        assert_eq!(15 + 256, state.validators[0].withdrawable_epoch);
+
+        // the queue is empty again later on
+        state.current_epoch = 20;
+        initiate_validator_exit(&mut state, 10);
+        assert_eq!(25, state.validators[10].exit_epoch);
+    }
+
+    #[test]
+    fn ejection() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.current_epoch = 10;
+        state.validators[0].balance = 16_500_000_000;
+        state.validators[0].effective_balance = 16_000_000_000;
+        state.validators[1].balance = 17_200_000_000;
+        state.validators[1].effective_balance = 17_000_000_000;
+        let mut state_totals = StateTotals::new(&state);
+
+        process_registry_updates(&mut state, &mut state_totals, &mut epoch_report_row);
+
+        assert_eq!(15, state.validators[0].exit_epoch);
+        assert_eq!(config::FAR_FUTURE_EPOCH, state.validators[1].exit_epoch);
+        assert_eq!(0, epoch_report_row.exited_validators);
+
+        // the validator is active until its exit epoch
+        for epoch in 11..16 {
+            state.current_epoch = epoch;
+            process_registry_updates(&mut state, &mut state_totals, &mut epoch_report_row);
+        }
+
+        assert!(!state.validators[0].is_active);
+        assert_eq!(1, epoch_report_row.exited_validators);
+        assert_eq!(15624, state.get_total_active_validators());
+        assert_eq!(15624, state_totals.active_validators);
+    }
+
+    #[test]
+    fn queue_validator_exits_in_batches() {
+        let mut state = State::from_config(Config::default());
+        state.current_epoch = 10;
+        initiate_validator_exit(&mut state, 0);
+
+        // the churn limit for 15,625 validators is 4
+        assert_eq!(
+            vec![(15, 3), (16, 4), (17, 2)],
+            queue_validator_exits(&mut state, 9)
+        );
+        assert_eq!(vec![(17, 1)], queue_validator_exits(&mut state, 1));
+    }
+}
@@ -0,0 +1,351 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates slashing block operations and `process_slashings` ops
+//   during the state transition
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use super::process_registry_updates::initiate_validator_exit;
+use crate::types::*;
+use rand::prelude::*;
+
+// simulates the `process_proposer_slashing` and `process_attester_slashing`
+//   operations included in the blocks of the epoch. The whistleblower is
+//   always the proposer of the block including the evidence.
+pub fn process_slashing_operations(
+    state: &mut State,
+    proposer_indices: &[usize],
+    epoch_report_row: &mut EpochReportRow,
+) {
+    let mut slashed_indices = vec![];
+    let epoch = state.current_epoch;
+
+    for (index, validator) in state.validators.iter().enumerate() {
+        // SPEC: is_slashable_validator
+        if !validator.is_slashable_validator(epoch) {
+            continue;
+        }
+
+        // equivocations of the previous epoch are always reported
+        if validator.has_equivocated
+            || (validator.is_active
+                && state.config.probability_slashed > 0.0
+                && state.config.probability_slashed > state.rng.gen())
+        {
+            slashed_indices.push(index);
+        }
+    }
+
+    let scheduled: u64 = state
+        .config
+        .slashing_schedule
+        .iter()
+        .filter(|(epoch, _)| *epoch as u64 == state.current_epoch)
+        .map(|(_, count)| count)
+        .sum();
+
+    if scheduled > 0 {
+        let candidates: Vec<usize> = state
+            .validators
+            .iter()
+            .enumerate()
+            .filter(|(index, v)| {
+                v.is_active && v.is_slashable_validator(epoch) && !slashed_indices.contains(index)
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        slashed_indices.extend(candidates.choose_multiple(&mut state.rng, scheduled as usize));
+    }
+
+    for slashed_index in slashed_indices {
+        let whistleblower_index = *proposer_indices.choose(&mut state.rng).unwrap();
+        let deltas = slash_validator(state, slashed_index, whistleblower_index);
+
+        epoch_report_row.aggregate(&deltas);
+        epoch_report_row.slashed_validators += 1;
+    }
+}
+
+pub fn slash_validator(
+    state: &mut State,
+    slashed_index: usize,
+    whistleblower_index: usize,
+) -> Deltas {
+    // SPEC
+    /*
+        epoch = get_current_epoch(state)
+        initiate_validator_exit(state, slashed_index)
+        validator = state.validators[slashed_index]
+        validator.slashed = True
+        validator.withdrawable_epoch = max(validator.withdrawable_epoch, Epoch(epoch + EPOCHS_PER_SLASHINGS_VECTOR))
+        state.slashings[epoch % EPOCHS_PER_SLASHINGS_VECTOR] += validator.effective_balance
+        decrease_balance(state, slashed_index, validator.effective_balance // MIN_SLASHING_PENALTY_QUOTIENT)
+
+        # Apply proposer and whistleblower rewards
+        proposer_index = get_beacon_proposer_index(state)
+        if whistleblower_index is None:
+            whistleblower_index = proposer_index
+        whistleblower_reward = Gwei(validator.effective_balance // WHISTLEBLOWER_REWARD_QUOTIENT)
+        proposer_reward = Gwei(whistleblower_reward // PROPOSER_REWARD_QUOTIENT)
+        increase_balance(state, proposer_index, proposer_reward)
+        increase_balance(state, whistleblower_index, Gwei(whistleblower_reward - proposer_reward))
+    */
+
+    let epoch = state.current_epoch;
+    let epochs_per_slashings_vector = state.config.spec.epochs_per_slashings_vector;
+    let mut deltas = Deltas::new();
+
+    initiate_validator_exit(state, slashed_index);
+
+    let validator = &mut state.validators[slashed_index];
+    validator.is_slashed = true;
+    validator.withdrawable_epoch = std::cmp::max(
+        validator.withdrawable_epoch,
+        epoch + epochs_per_slashings_vector,
+    );
+
+    let spec = &state.config.spec;
+    let min_slashing_penalty_quotient = if state.config.fork == Fork::Altair {
+        spec.min_slashing_penalty_quotient_altair
+    } else {
+        spec.min_slashing_penalty_quotient
+    };
+
+    let effective_balance = validator.effective_balance;
+    deltas.slashing_penalty = effective_balance / min_slashing_penalty_quotient;
+    validator.balance = validator.balance.saturating_sub(deltas.slashing_penalty);
+
+    state.slashings[(epoch % epochs_per_slashings_vector) as usize] += effective_balance;
+
+    // proposer and whistleblower are the same validator, and get both
+    //   the proposer reward and the rest of the whistleblower reward
+    deltas.whistleblower_reward = effective_balance / config::WHISTLEBLOWER_REWARD_QUOTIENT;
+    state.validators[whistleblower_index].balance += deltas.whistleblower_reward;
+
+    deltas
+}
+
+pub fn get_adjusted_total_slashing_balance(state: &State, total_balance: u64) -> u64 {
+    let total_slashing_balance: u64 = state.slashings.iter().sum();
+    let proportional_slashing_multiplier = if state.config.fork == Fork::Altair {
+        state.config.spec.proportional_slashing_multiplier_altair
+    } else {
+        config::PROPORTIONAL_SLASHING_MULTIPLIER
+    };
+
+    std::cmp::min(
+        total_slashing_balance * proportional_slashing_multiplier,
+        total_balance,
+    )
+}
+
+pub fn process_slashings(
+    validator: &Validator,
+    epoch: u64,
+    total_balance: u64,
+    adjusted_total_slashing_balance: u64,
+    spec: &SpecConstants,
+    deltas: &mut Deltas,
+) {
+    // SPEC
+    /*
+        for index, validator in enumerate(state.validators):
+            if validator.slashed and epoch + EPOCHS_PER_SLASHINGS_VECTOR // 2 == validator.withdrawable_epoch:
+                increment = EFFECTIVE_BALANCE_INCREMENT  # Factored out from penalty numerator to avoid uint64 overflow
+                penalty_numerator = validator.effective_balance // increment * adjusted_total_slashing_balance
+                penalty = penalty_numerator // total_balance * increment
+                decrease_balance(state, ValidatorIndex(index), penalty)
+    */
+
+    if !validator.is_slashed
+        || epoch + spec.epochs_per_slashings_vector / 2 != validator.withdrawable_epoch
+    {
+        return;
+    }
+
+    let increment = spec.effective_balance_increment;
+    let penalty_numerator =
+        validator.effective_balance / increment * adjusted_total_slashing_balance;
+    deltas.slashing_penalty += penalty_numerator / total_balance * increment;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slash_validator_penalties_and_rewards() {
+        let mut state = State::from_config(Config::default());
+        state.current_epoch = 10;
+
+        let deltas = slash_validator(&mut state, 3, 7);
+
+        assert!(state.validators[3].is_slashed);
+        assert_eq!(15, state.validators[3].exit_epoch);
+        assert_eq!(10 + 8192, state.validators[3].withdrawable_epoch);
+        assert_eq!(32_000_000_000 - 250_000_000, state.validators[3].balance);
+        assert_eq!(32_000_000_000 + 62_500_000, state.validators[7].balance);
+        assert_eq!(32_000_000_000, state.slashings[10]);
+        assert_eq!(250_000_000, deltas.slashing_penalty);
+        assert_eq!(62_500_000, deltas.whistleblower_reward);
+    }
+
+    #[test]
+    fn slashing_operations_schedule() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.current_epoch = 5;
+        state.config.probability_slashed = 0.0;
+        state.config.slashing_schedule = vec![(4, 100), (5, 10), (5, 2)];
+        let proposer_indices = state.pick_epoch_proposers();
+
+        process_slashing_operations(&mut state, &proposer_indices, &mut epoch_report_row);
+
+        let slashed = state.validators.iter().filter(|v| v.is_slashed).count();
+        assert_eq!(12, slashed);
+        assert_eq!(12, epoch_report_row.slashed_validators);
+        assert_eq!(12 * 250_000_000, epoch_report_row.deltas_slashing_penalties);
+        assert_eq!(
+            12 * 62_500_000,
+            epoch_report_row.deltas_whistleblower_rewards
+        );
+    }
+
+    #[test]
+    fn slashing_operations_probability() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        let proposer_indices = state.pick_epoch_proposers();
+
+        state.config.probability_slashed = 1.0;
+        state.validators[0].is_active = false;
+
+        process_slashing_operations(&mut state, &proposer_indices, &mut epoch_report_row);
+
+        assert!(!state.validators[0].is_slashed);
+        assert!(state.validators[1..].iter().all(|v| v.is_slashed));
+    }
+
+    #[test]
+    fn slashing_operations_equivocation() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        let proposer_indices = state.pick_epoch_proposers();
+
+        state.config.probability_slashed = 0.0;
+        state.validators[4].has_equivocated = true;
+        state.validators[9].has_equivocated = true;
+
+        process_slashing_operations(&mut state, &proposer_indices, &mut epoch_report_row);
+
+        assert!(state.validators[4].is_slashed);
+        assert!(state.validators[9].is_slashed);
+        assert_eq!(2, epoch_report_row.slashed_validators);
+    }
+
+    #[test]
+    fn slashing_operations_equivocation_not_slashable() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        let proposer_indices = state.pick_epoch_proposers();
+        state.current_epoch = 5;
+        state.config.probability_slashed = 0.0;
+        state.config.probability_honest = 0.0;
+        state.config.dishonest_behaviour = DishonestBehaviour::Equivocation;
+
+        // a validator still in the activation queue
+        let mut queued = Validator::new(32_000_000_000, &state.config.spec);
+        queued.activation_eligibility_epoch = 4;
+        let queued_index = state.validators.len();
+        let mut proposer_bitmap = state.get_proposer_bitmap(&proposer_indices);
+        proposer_bitmap.push(0);
+
+        let state_config = state.config.clone();
+        queued.update_previous_epoch_activity(
+            &state_config,
+            &mut state.rng,
+            &proposer_bitmap,
+            queued_index,
+        );
+        assert!(!queued.has_equivocated);
+
+        // even if it was flagged, it cannot be slashed before its activation
+        queued.has_equivocated = true;
+        state.validators.push(queued);
+
+        process_slashing_operations(&mut state, &proposer_indices, &mut epoch_report_row);
+
+        let queued = &state.validators[queued_index];
+        assert!(!queued.is_slashed);
+        assert_eq!(config::FAR_FUTURE_EPOCH, queued.exit_epoch);
+        assert_eq!(0, epoch_report_row.slashed_validators);
+        assert!(state.slashings.iter().all(|slashed| *slashed == 0));
+    }
+
+    #[test]
+    fn altair_slashings() {
+        let mut state = State::from_config(Config {
+            fork: Fork::Altair,
+            ..Config::default()
+        });
+        let total_balance = state.get_total_active_balance();
+
+        // twice the initial penalty, and twice the slashed balance counted
+        let deltas = slash_validator(&mut state, 3, 7);
+        assert_eq!(500_000_000, deltas.slashing_penalty);
+        assert_eq!(
+            2 * 32_000_000_000,
+            get_adjusted_total_slashing_balance(&state, total_balance)
+        );
+    }
+
+    #[test]
+    fn midway_penalty() {
+        let mut state = State::from_config(Config::default());
+        let total_balance = state.get_total_active_balance();
+        state.current_epoch = 0;
+
+        // 1/10th of the stake is slashed at epoch 0
+        for index in 0..1_562 {
+            slash_validator(&mut state, index, 2_000);
+        }
+        let adjusted_total_slashing_balance =
+            get_adjusted_total_slashing_balance(&state, total_balance);
+        assert_eq!(1_562 * 32_000_000_000, adjusted_total_slashing_balance);
+
+        // not yet at the middle of the withdrawability period
+        let mut deltas = Deltas::new();
+        process_slashings(
+            &state.validators[0],
+            4_095,
+            total_balance,
+            adjusted_total_slashing_balance,
+            &state.config.spec,
+            &mut deltas,
+        );
+        assert_eq!(0, deltas.slashing_penalty);
+
+        process_slashings(
+            &state.validators[0],
+            4_096,
+            total_balance,
+            adjusted_total_slashing_balance,
+            &state.config.spec,
+            &mut deltas,
+        );
+        assert_eq!(3_000_000_000, deltas.slashing_penalty);
+
+        // unslashed validators are not affected
+        let mut deltas = Deltas::new();
+        process_slashings(
+            &state.validators[3_000],
+            4_096,
+            total_balance,
+            adjusted_total_slashing_balance,
+            &state.config.spec,
+            &mut deltas,
+        );
+        assert_eq!(0, deltas.slashing_penalty);
+    }
+}
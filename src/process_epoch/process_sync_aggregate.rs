@@ -0,0 +1,290 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates the Altair sync committee, and the `process_sync_aggregate`
+//   block operations
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::types::*;
+use rand::prelude::*;
+
+// the committee is picked at the start of each period. Its members sign the
+//   block of each slot of the epoch, if the proposer was online, with the
+//   online probability.
+#[allow(clippy::manual_is_multiple_of)]
+pub fn process_sync_aggregates(
+    state: &mut State,
+    state_totals: &StateTotals,
+    proposer_indices: &[usize],
+    proposer_availability: &[bool],
+    epoch_report_row: &mut EpochReportRow,
+) {
+    if state.config.fork != Fork::Altair {
+        return;
+    }
+
+    let period = state.config.spec.epochs_per_sync_committee_period;
+    if state.current_epoch % period == 0 || state.sync_committee.is_empty() {
+        state.sync_committee = state.pick_sync_committee();
+    }
+
+    let (participant_reward, proposer_reward) = get_sync_rewards(state_totals, &state.config.spec);
+    let mut deltas = Deltas::new();
+
+    for (slot, proposer_index) in proposer_indices.iter().enumerate() {
+        // no block, no sync aggregate
+        if !proposer_availability[slot] {
+            continue;
+        }
+
+        for seat in 0..state.sync_committee.len() {
+            let participant_index = state.sync_committee[seat];
+
+            // SPEC
+            /*
+                for participant_index, participation_bit in zip(committee_indices, sync_aggregate.sync_committee_bits):
+                    if participation_bit:
+                        increase_balance(state, participant_index, participant_reward)
+                        increase_balance(state, get_beacon_proposer_index(state), proposer_reward)
+                    else:
+                        decrease_balance(state, participant_index, participant_reward)
+            */
+
+            if state.config.probability_online > state.rng.gen() {
+                state.validators[participant_index].balance += participant_reward;
+                state.validators[*proposer_index].balance += proposer_reward;
+
+                deltas.sync_reward += participant_reward;
+                deltas.proposer_reward += proposer_reward;
+            } else {
+                let participant = &mut state.validators[participant_index];
+                let penalty = std::cmp::min(participant_reward, participant.balance);
+                participant.balance -= penalty;
+
+                deltas.sync_penalty += penalty;
+            }
+        }
+    }
+
+    epoch_report_row.aggregate(&deltas);
+}
+
+// the cohorts and analytic engines do not pick a committee: a validator
+//   holds the expected number of seats for its effective balance, and signs
+//   the expected blocks of the epoch with the online probability
+pub fn get_expected_sync_deltas(
+    validator: &Validator,
+    state: &State,
+    state_totals: &StateTotals,
+    deltas: &mut Deltas,
+) {
+    if state.config.fork != Fork::Altair {
+        return;
+    }
+
+    let spec = &state.config.spec;
+    let (participant_reward, proposer_reward) = get_sync_rewards(state_totals, spec);
+    let probability_online = state.config.probability_online as f64;
+
+    let expected_seats = spec.sync_committee_size as f64 * validator.effective_balance as f64
+        / state_totals.active_balance as f64;
+    let expected_blocks = spec.slots_per_epoch as f64 * probability_online;
+    let expected_signatures = expected_seats * expected_blocks;
+
+    deltas.sync_reward =
+        (expected_signatures * probability_online * participant_reward as f64).round() as u64;
+    deltas.sync_penalty =
+        (expected_signatures * (1.0 - probability_online) * participant_reward as f64).round()
+            as u64;
+
+    // the block of a proposer is taken as published when its own
+    //   attestation was included
+    if validator.is_proposer && validator.has_matched_source {
+        deltas.proposer_reward +=
+            (spec.sync_committee_size as f64 * probability_online * proposer_reward as f64).round()
+                as u64;
+    }
+}
+
+// returns (participant reward, proposer reward) of a seat of the committee
+//   in a slot
+pub fn get_sync_rewards(state_totals: &StateTotals, spec: &SpecConstants) -> (u64, u64) {
+    // SPEC
+    /*
+        total_active_increments = get_total_active_balance(state) // EFFECTIVE_BALANCE_INCREMENT
+        total_base_rewards = Gwei(get_base_reward_per_increment(state) * total_active_increments)
+        max_participant_rewards = Gwei(total_base_rewards * SYNC_REWARD_WEIGHT // WEIGHT_DENOMINATOR // SLOTS_PER_EPOCH)
+        participant_reward = Gwei(max_participant_rewards // SYNC_COMMITTEE_SIZE)
+        proposer_reward = Gwei(participant_reward * PROPOSER_WEIGHT // (WEIGHT_DENOMINATOR - PROPOSER_WEIGHT))
+    */
+
+    let total_active_increments = state_totals.active_balance / spec.effective_balance_increment;
+    let total_base_rewards = spec.get_base_reward_per_increment(state_totals.sqrt_active_balance)
+        * total_active_increments;
+    let max_participant_rewards = total_base_rewards * spec.sync_reward_weight
+        / spec.weight_denominator
+        / spec.slots_per_epoch;
+    let participant_reward = max_participant_rewards / spec.sync_committee_size;
+    let proposer_reward = participant_reward * spec.proposer_weight
+        / (spec.weight_denominator - spec.proposer_weight);
+
+    (participant_reward, proposer_reward)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_altair_state(probability_online: f32) -> State {
+        State::from_config(Config {
+            fork: Fork::Altair,
+            probability_online,
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn get_sync_rewards_mainnet() {
+        let state = get_altair_state(1.0);
+        let state_totals = StateTotals::new(&state);
+
+        // 2_862 Gwei per increment, over 500,000 increments
+        let (participant_reward, proposer_reward) =
+            get_sync_rewards(&state_totals, &state.config.spec);
+        assert_eq!(2_862 * 500_000 * 2 / 64 / 32 / 512, participant_reward);
+        assert_eq!(participant_reward * 8 / 56, proposer_reward);
+    }
+
+    #[test]
+    fn full_participation() {
+        let mut state = get_altair_state(1.0);
+        let state_totals = StateTotals::new(&state);
+        let mut epoch_report_row = EpochReportRow::open(0);
+        let proposer_indices = state.pick_epoch_proposers();
+        let staked_balance = state.get_total_staked_balance();
+
+        process_sync_aggregates(
+            &mut state,
+            &state_totals,
+            &proposer_indices,
+            &[true; 32],
+            &mut epoch_report_row,
+        );
+
+        let (participant_reward, proposer_reward) =
+            get_sync_rewards(&state_totals, &state.config.spec);
+        assert_eq!(512, state.sync_committee.len());
+        assert_eq!(
+            32 * 512 * participant_reward,
+            epoch_report_row.deltas_sync_rewards
+        );
+        assert_eq!(
+            32 * 512 * proposer_reward,
+            epoch_report_row.deltas_proposer_rewards
+        );
+        assert_eq!(0, epoch_report_row.deltas_sync_penalties);
+        assert_eq!(
+            staked_balance + 32 * 512 * (participant_reward + proposer_reward),
+            state.get_total_staked_balance()
+        );
+    }
+
+    #[test]
+    fn offline_committee_and_proposers() {
+        let mut state = get_altair_state(0.0);
+        let state_totals = StateTotals::new(&state);
+        let mut epoch_report_row = EpochReportRow::open(0);
+        let proposer_indices = state.pick_epoch_proposers();
+
+        // the members are penalized only for the slots with a block
+        let mut proposer_availability = [false; 32];
+        proposer_availability[3] = true;
+        process_sync_aggregates(
+            &mut state,
+            &state_totals,
+            &proposer_indices,
+            &proposer_availability,
+            &mut epoch_report_row,
+        );
+
+        let (participant_reward, _) = get_sync_rewards(&state_totals, &state.config.spec);
+        assert_eq!(0, epoch_report_row.deltas_sync_rewards);
+        assert_eq!(
+            512 * participant_reward,
+            epoch_report_row.deltas_sync_penalties
+        );
+    }
+
+    #[test]
+    fn committee_period() {
+        let mut state = get_altair_state(1.0);
+        let state_totals = StateTotals::new(&state);
+        let proposer_indices = state.pick_epoch_proposers();
+        let process_epoch_at = |state: &mut State, epoch| {
+            state.current_epoch = epoch;
+            process_sync_aggregates(
+                state,
+                &state_totals,
+                &proposer_indices,
+                &[true; 32],
+                &mut EpochReportRow::open(0),
+            );
+            state.sync_committee.clone()
+        };
+
+        let first_committee = process_epoch_at(&mut state, 0);
+        assert_eq!(first_committee, process_epoch_at(&mut state, 255));
+        assert_ne!(first_committee, process_epoch_at(&mut state, 256));
+    }
+
+    #[test]
+    fn expected_sync_deltas() {
+        let state = get_altair_state(0.5);
+        let state_totals = StateTotals::new(&state);
+        let (participant_reward, proposer_reward) =
+            get_sync_rewards(&state_totals, &state.config.spec);
+
+        // 512 seats over 15,625 validators, 16 blocks, half of them signed
+        let mut validator = Validator::new(32_000_000_000, &state.config.spec);
+        let mut deltas = Deltas::new();
+        get_expected_sync_deltas(&validator, &state, &state_totals, &mut deltas);
+
+        let signed = 512.0 / 15_625.0 * 16.0 * 0.5 * participant_reward as f64;
+        assert_eq!(signed.round() as u64, deltas.sync_reward);
+        assert_eq!(signed.round() as u64, deltas.sync_penalty);
+        assert_eq!(0, deltas.proposer_reward);
+
+        // the proposer of a published block, with half of the committee online
+        validator.is_proposer = true;
+        validator.has_matched_source = true;
+        let mut deltas = Deltas::new();
+        get_expected_sync_deltas(&validator, &state, &state_totals, &mut deltas);
+        assert_eq!(256 * proposer_reward, deltas.proposer_reward);
+
+        let mut state = state;
+        state.config.fork = Fork::Phase0;
+        let mut deltas = Deltas::new();
+        get_expected_sync_deltas(&validator, &state, &state_totals, &mut deltas);
+        assert_eq!(
+            0,
+            deltas.sync_reward + deltas.sync_penalty + deltas.proposer_reward
+        );
+    }
+
+    #[test]
+    fn phase0() {
+        let mut state = State::from_config(Config::default());
+        let state_totals = StateTotals::new(&state);
+        let proposer_indices = state.pick_epoch_proposers();
+
+        process_sync_aggregates(
+            &mut state,
+            &state_totals,
+            &proposer_indices,
+            &[true; 32],
+            &mut EpochReportRow::open(0),
+        );
+
+        assert!(state.sync_committee.is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Simulates `process_voluntary_exit` block operations following an exit policy
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use super::process_registry_updates::initiate_validator_exit;
+use crate::types::*;
+use rand::prelude::*;
+
+pub fn process_voluntary_exits(
+    state: &mut State,
+    last_epoch_report_row: Option<&EpochReportRow>,
+    epoch_report_row: &mut EpochReportRow,
+) {
+    let exits = match state.config.exit_policy {
+        ExitPolicy::None => return,
+        ExitPolicy::FixedRate(n) => n,
+        ExitPolicy::AprBelow(threshold, n) => match last_epoch_report_row {
+            Some(row) if get_annualized_net_reward_rate(row) < threshold => n,
+            _ => return,
+        },
+        // the lowest indices are genesis validators, which can only exit
+        //   after SHARD_COMMITTEE_PERIOD epochs, so an earlier batch waits
+        ExitPolicy::Batch(epoch, n)
+            if std::cmp::max(epoch as u64, state.config.spec.shard_committee_period)
+                == state.current_epoch =>
+        {
+            n
+        }
+        ExitPolicy::Batch(_, _) => return,
+    };
+
+    // SPEC
+    /*
+        # Verify the validator is active
+        assert is_active_validator(validator, get_current_epoch(state))
+        # Verify exit has not been initiated
+        assert validator.exit_epoch == FAR_FUTURE_EPOCH
+        # Exits must specify an epoch when they become valid; they are not valid before then
+        assert get_current_epoch(state) >= voluntary_exit.epoch
+        # Verify the validator has been active long enough
+        assert get_current_epoch(state) >= validator.activation_epoch + SHARD_COMMITTEE_PERIOD
+    */
+
+    let current_epoch = state.current_epoch;
+    let shard_committee_period = state.config.spec.shard_committee_period;
+    let candidates: Vec<usize> = state
+        .validators
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| {
+            v.is_active_validator(current_epoch)
+                && v.exit_epoch == config::FAR_FUTURE_EPOCH
+                && current_epoch >= v.activation_epoch + shard_committee_period
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    let exiting_indices: Vec<usize> = match state.config.exit_policy {
+        ExitPolicy::Batch(_, _) => candidates.into_iter().take(exits as usize).collect(),
+        _ => candidates
+            .choose_multiple(&mut state.rng, exits as usize)
+            .cloned()
+            .collect(),
+    };
+
+    for index in exiting_indices {
+        initiate_validator_exit(state, index);
+        epoch_report_row.voluntary_exits += 1;
+    }
+}
+
+// net rewards of the epoch over the total effective balance, in a year
+pub fn get_annualized_net_reward_rate(row: &EpochReportRow) -> f64 {
+    let rewards = row.get_vote_rewards()
+        + row.deltas_proposer_rewards
+        + row.deltas_attester_rewards
+        + row.deltas_whistleblower_rewards
+        + row.deltas_sync_rewards;
+    let penalties = row.get_vote_penalties()
+        + row.deltas_inactivity_penalties
+        + row.deltas_slashing_penalties
+        + row.deltas_sync_penalties;
+
+    (rewards as f64 - penalties as f64) / row.total_effective_balance as f64
+        * config::EPOCHS_PER_YEAR as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_row(rewards: u64, penalties: u64, total_effective_balance: u64) -> EpochReportRow {
+        let mut row = EpochReportRow::open(0);
+        row.deltas_attester_rewards = rewards;
+        row.deltas_source_penalties = penalties;
+        row.total_effective_balance = total_effective_balance;
+
+        row
+    }
+
+    fn count_exits(state: &State) -> usize {
+        state
+            .validators
+            .iter()
+            .filter(|v| v.exit_epoch != config::FAR_FUTURE_EPOCH)
+            .count()
+    }
+
+    #[test]
+    fn annualized_net_reward_rate() {
+        let row = get_row(1_000, 0, 82_125_000);
+        assert_eq!(1.0, get_annualized_net_reward_rate(&row));
+
+        let row = get_row(1_000, 1_500, 82_125_000);
+        assert_eq!(-0.5, get_annualized_net_reward_rate(&row));
+    }
+
+    #[test]
+    fn fixed_rate() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.config.exit_policy = ExitPolicy::FixedRate(10);
+
+        // validators need to be active for SHARD_COMMITTEE_PERIOD epochs
+        state.current_epoch = 255;
+        process_voluntary_exits(&mut state, None, &mut epoch_report_row);
+        assert_eq!(0, count_exits(&state));
+
+        state.current_epoch = 256;
+        process_voluntary_exits(&mut state, None, &mut epoch_report_row);
+        assert_eq!(10, count_exits(&state));
+        assert_eq!(10, epoch_report_row.voluntary_exits);
+    }
+
+    #[test]
+    fn apr_below() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.config.exit_policy = ExitPolicy::AprBelow(0.05, 10);
+        state.current_epoch = 300;
+
+        process_voluntary_exits(&mut state, None, &mut epoch_report_row);
+        assert_eq!(0, count_exits(&state));
+
+        let row = get_row(60_000, 0, 82_125_000 * 1_000);
+        process_voluntary_exits(&mut state, Some(&row), &mut epoch_report_row);
+        assert_eq!(0, count_exits(&state));
+
+        let row = get_row(40_000, 0, 82_125_000 * 1_000);
+        process_voluntary_exits(&mut state, Some(&row), &mut epoch_report_row);
+        assert_eq!(10, count_exits(&state));
+    }
+
+    #[test]
+    fn batch() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.config.exit_policy = ExitPolicy::Batch(300, 100);
+
+        state.current_epoch = 299;
+        process_voluntary_exits(&mut state, None, &mut epoch_report_row);
+        assert_eq!(0, count_exits(&state));
+
+        state.current_epoch = 300;
+        process_voluntary_exits(&mut state, None, &mut epoch_report_row);
+        assert_eq!(100, count_exits(&state));
+        assert!(state.validators[..100]
+            .iter()
+            .all(|v| v.exit_epoch != config::FAR_FUTURE_EPOCH));
+
+        // the exit queue takes its time
+        assert_eq!(305, state.validators[0].exit_epoch);
+        assert_eq!(329, state.validators[99].exit_epoch);
+    }
+
+    #[test]
+    fn batch_before_shard_committee_period() {
+        let mut state = State::from_config(Config::default());
+        let mut epoch_report_row = EpochReportRow::open(0);
+        state.config.exit_policy = ExitPolicy::Batch(100, 10);
+
+        // pushed back to the first epoch the validators can exit
+        state.current_epoch = 100;
+        process_voluntary_exits(&mut state, None, &mut epoch_report_row);
+        assert_eq!(0, count_exits(&state));
+
+        state.current_epoch = 256;
+        process_voluntary_exits(&mut state, None, &mut epoch_report_row);
+        assert_eq!(10, count_exits(&state));
+    }
+}
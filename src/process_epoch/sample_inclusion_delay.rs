@@ -0,0 +1,166 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Samples the inclusion of attestations in the blocks of the epoch, instead
+//   of using the closed form expected value of the inclusion delay
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::types::*;
+use rand::prelude::*;
+
+pub struct Inclusion {
+    // in slots, at least 1
+    pub delay: u64,
+
+    // slot of the epoch whose proposer included the attestation
+    pub proposer_slot: usize,
+}
+
+// whether the proposer of each slot of the epoch is online, and thus
+//   proposes a block including attestations
+pub fn sample_proposer_availability(config: &Config, rng: &mut StdRng) -> Vec<bool> {
+    (0..config.spec.slots_per_epoch)
+        .map(|_| config.probability_online > rng.gen())
+        .collect()
+}
+
+// None if the validator has no attestation to include, if no proposer was
+//   online to include it, or if the inclusion delay is not sampled
+pub fn sample_inclusion(
+    validator: &Validator,
+    previous_epoch: u64,
+    config: &Config,
+    rng: &mut StdRng,
+    proposer_availability: &[bool],
+) -> Option<Inclusion> {
+    if !validator.has_matched_source || !validator.is_eligible(previous_epoch) {
+        return None;
+    }
+
+    let slots = config.spec.slots_per_epoch;
+    let attestation_slot = rng.gen_range(0, slots);
+
+    let delay = match &config.inclusion_delay {
+        InclusionDelay::Expected => None,
+
+        // geometric on the availability of the next proposers
+        InclusionDelay::Sampled => (1..=slots)
+            .find(|delay| proposer_availability[((attestation_slot + delay) % slots) as usize]),
+
+        // sampled from the weights, then up to the next online proposer
+        InclusionDelay::Empirical(_) => match &config.inclusion_delay_distribution {
+            Some(distribution) => {
+                let sampled_delay = distribution.sample(rng) as u64 + 1;
+                (sampled_delay..=slots).find(|delay| {
+                    proposer_availability[((attestation_slot + delay) % slots) as usize]
+                })
+            }
+            None => panic!("the empirical inclusion delay needs a built config"),
+        },
+    };
+
+    delay.map(|delay| Inclusion {
+        delay,
+        proposer_slot: ((attestation_slot + delay) % slots) as usize,
+    })
+}
+
+// whether the attestation of the validator made it into a block, and so
+//   into the matching attestations. With the expected inclusion delay,
+//   every attestation is assumed to be included.
+pub fn is_included(inclusion: Option<&Inclusion>, config: &Config) -> bool {
+    config.inclusion_delay == InclusionDelay::Expected || inclusion.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(state: &mut State, proposer_availability: &[bool]) -> Option<Inclusion> {
+        let previous_epoch = state.get_previous_epoch();
+
+        sample_inclusion(
+            &state.validators[0],
+            previous_epoch,
+            &state.config,
+            &mut state.rng,
+            proposer_availability,
+        )
+    }
+
+    fn get_attesting_state() -> State {
+        let mut state = State::from_config(Config::default());
+        state.validators[0].has_matched_source = true;
+
+        state
+    }
+
+    #[test]
+    fn expected_inclusion_delay() {
+        let mut state = get_attesting_state();
+        state.config.inclusion_delay = InclusionDelay::Expected;
+
+        let proposer_availability = vec![true; 32];
+        assert!(sample(&mut state, &proposer_availability).is_none());
+    }
+
+    #[test]
+    fn sampled_inclusion_delay() {
+        let mut state = get_attesting_state();
+        state.config.inclusion_delay = InclusionDelay::Sampled;
+
+        // with every proposer online, attestations are included in the next slot
+        let proposer_availability = vec![true; 32];
+        let inclusion = sample(&mut state, &proposer_availability);
+        assert_eq!(1, inclusion.unwrap().delay);
+
+        // with a single proposer online, it includes every attestation
+        let mut proposer_availability = vec![false; 32];
+        proposer_availability[7] = true;
+        for _ in 0..10 {
+            let inclusion = sample(&mut state, &proposer_availability);
+            assert_eq!(7, inclusion.unwrap().proposer_slot);
+        }
+
+        // no proposer online, no inclusion
+        let proposer_availability = vec![false; 32];
+        assert!(sample(&mut state, &proposer_availability).is_none());
+
+        // no attestation, no inclusion
+        let proposer_availability = vec![true; 32];
+        state.validators[0].has_matched_source = false;
+        assert!(sample(&mut state, &proposer_availability).is_none());
+    }
+
+    #[test]
+    fn empirical_inclusion_delay() {
+        let config = Config {
+            inclusion_delay: InclusionDelay::Empirical(vec![0.0, 0.0, 1.0]),
+            ..Config::default()
+        };
+        let mut state = State::from_config(config.build().unwrap());
+        state.validators[0].has_matched_source = true;
+
+        let proposer_availability = vec![true; 32];
+        let inclusion = sample(&mut state, &proposer_availability);
+        assert_eq!(3, inclusion.unwrap().delay);
+
+        // with a single proposer online, the attestations it can reach after
+        //   the sampled delay are all included by it
+        let mut proposer_availability = vec![false; 32];
+        proposer_availability[7] = true;
+        let mut included = 0;
+        for _ in 0..20 {
+            if let Some(inclusion) = sample(&mut state, &proposer_availability) {
+                assert_eq!(7, inclusion.proposer_slot);
+                assert!(inclusion.delay >= 3);
+                included += 1;
+            }
+        }
+        assert!(included > 0);
+
+        // no proposer online, no inclusion
+        let proposer_availability = vec![false; 32];
+        assert!(sample(&mut state, &proposer_availability).is_none());
+    }
+}
@@ -0,0 +1,178 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Runs the simulation for a given config, epoch by epoch
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::process_epoch::*;
+use crate::types::*;
+
+pub struct Simulation {
+    pub state: State,
+    pub state_totals: StateTotals,
+    pub output: Output,
+
+    // the epoch to be processed by the next step
+    next_epoch: i32,
+
+    // of the config, restored once an outage is over
+    probability_online: f32,
+}
+
+impl Simulation {
+    // the config is checked, and its pre-computations filled, here
+    pub fn new(config: Config) -> Result<Simulation, String> {
+        let config = config.build()?;
+        let probability_online = config.probability_online;
+        let state = State::from_config(config);
+        let state_totals = StateTotals::new(&state);
+
+        Ok(Simulation {
+            state,
+            state_totals,
+            output: Output::new(),
+            next_epoch: 0,
+            probability_online,
+        })
+    }
+
+    // processes the next epoch with the engine of the config, and returns
+    //   its row of the report
+    pub fn step(&mut self) -> &EpochReportRow {
+        let state = &mut self.state;
+        let state_totals = &mut self.state_totals;
+        let output = &mut self.output;
+        let epoch_id = self.next_epoch;
+
+        let probability_online = match state.config.get_outage(epoch_id) {
+            Some(outage) => outage.probability_online,
+            None => self.probability_online,
+        };
+        // the attesting balances follow the votes of the epoch
+        if probability_online != state.config.probability_online {
+            state.config.set_probability_online(probability_online);
+            state_totals.update_attesting_balances(&state.config);
+        }
+
+        match state.config.engine {
+            Engine::Validators => process_epoch(state, state_totals, epoch_id, output),
+            Engine::Cohorts => process_epoch_cohorts(state, state_totals, epoch_id, output),
+            Engine::Analytic => process_epoch_analytic(state, state_totals, epoch_id, output),
+        }
+        self.next_epoch += 1;
+
+        self.output.rows.last().unwrap()
+    }
+
+    // processes the epochs of the config left
+    pub fn run(mut self) -> (State, Output) {
+        while self.next_epoch < self.state.config.epochs {
+            self.step();
+        }
+
+        (self.state, self.output)
+    }
+
+    // run i uses the seed + i, so any run can be reproduced on its own
+    pub fn run_monte_carlo(config: &Config) -> Result<Vec<Output>, String> {
+        (0..config.runs)
+            .map(|run| {
+                let mut config = config.clone();
+                config.seed = config.seed.wrapping_add(run);
+
+                Ok(Simulation::new(config)?.run().1)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_and_run() {
+        let config = Config {
+            epochs: 4,
+            seed: 1,
+            ..Config::default()
+        };
+
+        let mut simulation = Simulation::new(config.clone()).unwrap();
+        assert_eq!(0, simulation.step().epoch_id);
+        assert_eq!(1, simulation.step().epoch_id);
+
+        // the run goes on from the last step, with the same outcome
+        let (stepped_state, stepped_output) = simulation.run();
+        let (state, output) = Simulation::new(config).unwrap().run();
+
+        assert_eq!(4, output.rows.len());
+        assert_eq!(output.rows.len(), stepped_output.rows.len());
+        assert_eq!(
+            state.get_total_staked_balance(),
+            stepped_state.get_total_staked_balance()
+        );
+    }
+
+    #[test]
+    fn run_monte_carlo_seeds() {
+        let mut config = Config {
+            epochs: 2,
+            runs: 2,
+            seed: 1,
+            ..Config::default()
+        };
+
+        let outputs = Simulation::run_monte_carlo(&config).unwrap();
+        config.seed = 2;
+        let (_, second_output) = Simulation::new(config).unwrap().run();
+
+        // every column but the time, the last one
+        let columns = EPOCH_REPORT_HEADER.len() - 1;
+        assert_eq!(2, outputs.len());
+        assert_eq!(
+            outputs[1].rows[1].get_values()[..columns],
+            second_output.rows[1].get_values()[..columns]
+        );
+    }
+
+    #[test]
+    fn new_builds_the_config() {
+        let simulation = Simulation::new(Config {
+            probability_online: 1.5,
+            ..Config::default()
+        });
+
+        assert_eq!(
+            Some("probability_online should be in the interval [0,1]".to_string()),
+            simulation.err()
+        );
+    }
+
+    #[test]
+    fn step_outage() {
+        let config = Config {
+            epochs: 4,
+            probability_online: 1.0,
+            outages: vec![Outage {
+                from_epoch: 1,
+                to_epoch: 2,
+                probability_online: 0.0,
+            }],
+            ..Config::default()
+        };
+        let (state, output) = Simulation::new(config).unwrap().run();
+
+        let source_rewards: Vec<u64> = output
+            .rows
+            .iter()
+            .map(|row| row.deltas_source_rewards)
+            .collect();
+        assert!(source_rewards[0] > 0);
+        assert_eq!(vec![0, 0], source_rewards[1..3].to_vec());
+        assert!(source_rewards[3] > 0);
+
+        // the online probability is back to the one of the config
+        assert_eq!(1.0, state.config.probability_online);
+    }
+}
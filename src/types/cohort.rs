@@ -0,0 +1,50 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// Validators in the same state, tracked by count instead of one by one
+//
+////////////////////////////////////////////////////////////////////////////////
+
+// all of them active, unslashed, and with the same effective balance,
+//   inactivity score and exit epoch. Their balances are bucketed, so only
+//   the sum is kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cohort {
+    pub count: u64,
+    pub balance_sum: u64,
+    pub effective_balance: u64,
+    pub inactivity_score: u64,
+
+    // the analytic engine keeps an expected score, between inactivity_score
+    //   and inactivity_score + 1. Always 0 with the cohorts engine.
+    pub inactivity_score_fraction: f64,
+
+    // FAR_FUTURE_EPOCH until the cohort is ejected
+    pub exit_epoch: u64,
+}
+
+impl Cohort {
+    // the balance of each validator of the cohort
+    pub fn get_balance(&self) -> u64 {
+        self.balance_sum / self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::config;
+
+    #[test]
+    fn get_balance() {
+        let cohort = Cohort {
+            count: 3,
+            balance_sum: 96_000_000_002,
+            effective_balance: 32_000_000_000,
+            inactivity_score: 0,
+            inactivity_score_fraction: 0.0,
+            exit_epoch: config::FAR_FUTURE_EPOCH,
+        };
+
+        assert_eq!(32_000_000_000, cohort.get_balance());
+    }
+}
@@ -4,17 +4,324 @@
 //
 ////////////////////////////////////////////////////////////////////////////////
 
-extern crate clap;
+use super::Deposit;
+use rand::distributions::WeightedIndex;
 
-use clap::{App, Arg};
+// the constants of the spec that can be changed at runtime are in SpecConstants
+pub const JUSTIFICATION_BITS_LENGTH: usize = 4;
+pub const MIN_EPOCHS_TO_INACTIVITY_PENALTY: u64 = 4;
+pub const WHISTLEBLOWER_REWARD_QUOTIENT: u64 = 512;
+pub const PROPORTIONAL_SLASHING_MULTIPLIER: u64 = 1;
+pub const MIN_PER_EPOCH_CHURN_LIMIT: u64 = 4;
+pub const MAX_SEED_LOOKAHEAD: u64 = 4;
+pub const EJECTION_BALANCE: u64 = 16_000_000_000;
+pub const MIN_VALIDATOR_WITHDRAWABILITY_DELAY: u64 = 256;
+pub const MAX_DEPOSITS: u64 = 16;
+pub const MIN_ATTESTATION_INCLUSION_DELAY: u64 = 1;
+pub const FAR_FUTURE_EPOCH: u64 = u64::MAX;
 
-pub const MAX_EFFECTIVE_BALANCE: u64 = 32_000_000_000;
-pub const BASE_REWARD_FACTOR: u64 = 64;
-pub const BASE_REWARDS_PER_EPOCH: u64 = 4;
-pub const PROPOSER_REWARD_QUOTIENT: u64 = 8;
-pub const EFFECTIVE_BALANCE_INCREMENT: u64 = 1_000_000_000;
+// constants of the spec, with the values of a preset unless overridden
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecConstants {
+    pub max_effective_balance: u64,
+    pub effective_balance_increment: u64,
+    pub base_reward_factor: u64,
+    pub base_rewards_per_epoch: u64,
+    pub proposer_reward_quotient: u64,
+    pub slots_per_epoch: u64,
+    pub inactivity_penalty_quotient: u64,
+    pub min_slashing_penalty_quotient: u64,
+    pub churn_limit_quotient: u64,
+    pub epochs_per_slashings_vector: u64,
+    pub shard_committee_period: u64,
 
-#[derive(Debug)]
+    // Altair participation flags, and the share of the proposer
+    pub timely_source_weight: u64,
+    pub timely_target_weight: u64,
+    pub timely_head_weight: u64,
+    pub proposer_weight: u64,
+    pub weight_denominator: u64,
+    pub inactivity_penalty_quotient_altair: u64,
+
+    // Altair slashings, harsher than the Phase 0 ones
+    pub min_slashing_penalty_quotient_altair: u64,
+    pub proportional_slashing_multiplier_altair: u64,
+
+    // Altair inactivity scores, the leak penalty growing with them
+    pub inactivity_score_bias: u64,
+    pub inactivity_score_recovery_rate: u64,
+
+    // Altair sync committee
+    pub sync_committee_size: u64,
+    pub epochs_per_sync_committee_period: u64,
+    pub sync_reward_weight: u64,
+}
+
+impl SpecConstants {
+    pub fn mainnet() -> SpecConstants {
+        SpecConstants {
+            max_effective_balance: 32_000_000_000,
+            effective_balance_increment: 1_000_000_000,
+            base_reward_factor: 64,
+            base_rewards_per_epoch: 4,
+            proposer_reward_quotient: 8,
+            slots_per_epoch: 32,
+            inactivity_penalty_quotient: 67_108_864,
+            min_slashing_penalty_quotient: 128,
+            churn_limit_quotient: 65_536,
+            epochs_per_slashings_vector: 8192,
+            shard_committee_period: 256,
+            timely_source_weight: 14,
+            timely_target_weight: 26,
+            timely_head_weight: 14,
+            proposer_weight: 8,
+            weight_denominator: 64,
+            inactivity_penalty_quotient_altair: 50_331_648,
+            min_slashing_penalty_quotient_altair: 64,
+            proportional_slashing_multiplier_altair: 2,
+            inactivity_score_bias: 4,
+            inactivity_score_recovery_rate: 16,
+            sync_committee_size: 512,
+            epochs_per_sync_committee_period: 256,
+            sync_reward_weight: 2,
+        }
+    }
+
+    // the testing configuration of the spec, with shorter epochs
+    pub fn minimal() -> SpecConstants {
+        SpecConstants {
+            slots_per_epoch: 8,
+            inactivity_penalty_quotient: 33_554_432,
+            min_slashing_penalty_quotient: 64,
+            churn_limit_quotient: 32,
+            epochs_per_slashings_vector: 64,
+            shard_committee_period: 64,
+            sync_committee_size: 32,
+            epochs_per_sync_committee_period: 8,
+            ..SpecConstants::mainnet()
+        }
+    }
+
+    pub fn from_preset(preset: &str) -> Result<SpecConstants, String> {
+        match preset.trim() {
+            "mainnet" => Ok(SpecConstants::mainnet()),
+            "minimal" => Ok(SpecConstants::minimal()),
+            _ => Err("spec only supports the 'mainnet' or 'minimal' presets".to_string()),
+        }
+    }
+
+    // overrides a constant, named as in the spec, e.g. BASE_REWARD_FACTOR
+    pub fn set(&mut self, name: &str, value: u64) -> Result<(), String> {
+        let constant = match name.trim().to_uppercase().as_str() {
+            "MAX_EFFECTIVE_BALANCE" => &mut self.max_effective_balance,
+            "EFFECTIVE_BALANCE_INCREMENT" => &mut self.effective_balance_increment,
+            "BASE_REWARD_FACTOR" => &mut self.base_reward_factor,
+            "BASE_REWARDS_PER_EPOCH" => &mut self.base_rewards_per_epoch,
+            "PROPOSER_REWARD_QUOTIENT" => &mut self.proposer_reward_quotient,
+            "SLOTS_PER_EPOCH" => &mut self.slots_per_epoch,
+            "INACTIVITY_PENALTY_QUOTIENT" => &mut self.inactivity_penalty_quotient,
+            "MIN_SLASHING_PENALTY_QUOTIENT" => &mut self.min_slashing_penalty_quotient,
+            "CHURN_LIMIT_QUOTIENT" => &mut self.churn_limit_quotient,
+            "EPOCHS_PER_SLASHINGS_VECTOR" => &mut self.epochs_per_slashings_vector,
+            "SHARD_COMMITTEE_PERIOD" => &mut self.shard_committee_period,
+            "TIMELY_SOURCE_WEIGHT" => &mut self.timely_source_weight,
+            "TIMELY_TARGET_WEIGHT" => &mut self.timely_target_weight,
+            "TIMELY_HEAD_WEIGHT" => &mut self.timely_head_weight,
+            "PROPOSER_WEIGHT" => &mut self.proposer_weight,
+            "WEIGHT_DENOMINATOR" => &mut self.weight_denominator,
+            "INACTIVITY_PENALTY_QUOTIENT_ALTAIR" => &mut self.inactivity_penalty_quotient_altair,
+            "MIN_SLASHING_PENALTY_QUOTIENT_ALTAIR" => {
+                &mut self.min_slashing_penalty_quotient_altair
+            }
+            "PROPORTIONAL_SLASHING_MULTIPLIER_ALTAIR" => {
+                &mut self.proportional_slashing_multiplier_altair
+            }
+            "INACTIVITY_SCORE_BIAS" => &mut self.inactivity_score_bias,
+            "INACTIVITY_SCORE_RECOVERY_RATE" => &mut self.inactivity_score_recovery_rate,
+            "SYNC_COMMITTEE_SIZE" => &mut self.sync_committee_size,
+            "EPOCHS_PER_SYNC_COMMITTEE_PERIOD" => &mut self.epochs_per_sync_committee_period,
+            "SYNC_REWARD_WEIGHT" => &mut self.sync_reward_weight,
+            _ => return Err(format!("unknown spec constant {}", name)),
+        };
+
+        *constant = value;
+        Ok(())
+    }
+
+    // SPEC: get_base_reward_per_increment of Altair
+    pub fn get_base_reward_per_increment(&self, sqrt_total_active_balance: u64) -> u64 {
+        self.effective_balance_increment * self.base_reward_factor / sqrt_total_active_balance
+    }
+
+    // parses lists of the form "NAME=value,NAME=value"
+    pub fn parse_overrides(overrides: &str) -> Result<Vec<(String, u64)>, String> {
+        overrides
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(|item| {
+                let mut parts = item.trim().split('=');
+                let name = parts.next().map(|p| p.trim().to_string());
+                let value = parts.next().and_then(|p| p.trim().parse().ok());
+
+                match (name, value, parts.next()) {
+                    (Some(name), Some(value), None) => Ok((name, value)),
+                    _ => Err("spec constants should be of the form NAME=value".to_string()),
+                }
+            })
+            .collect()
+    }
+}
+
+// (60 * 60 * 24 * 365) / (12 * 32)
+pub const EPOCHS_PER_YEAR: u64 = 82_125;
+
+// which validators leave voluntarily, and when
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExitPolicy {
+    None,
+
+    // a number of random validators at each epoch
+    FixedRate(u64),
+
+    // a number of random validators at each epoch, while the annualized
+    //   net reward rate of the last epoch is below a threshold
+    AprBelow(f64, u64),
+
+    // a number of validators at the given epoch, the lowest indices first,
+    //   as if they were run by a single operator
+    Batch(i32, u64),
+}
+
+// what a dishonest validator does with its attestation
+#[derive(Debug, Clone, PartialEq)]
+pub enum DishonestBehaviour {
+    // votes for a source other than the justified checkpoint, so the
+    //   attestation is not included at all
+    WrongSource,
+
+    // votes for a wrong target, and thus for a wrong head
+    WrongTarget,
+
+    // votes for a wrong head block
+    WrongHead,
+
+    // signs two conflicting attestations. One of them gets all the votes
+    //   right, but the validator is reported and slashed in the next epoch
+    Equivocation,
+}
+
+// how the inclusion delay of attestations is simulated
+#[derive(Debug, Clone, PartialEq)]
+pub enum InclusionDelay {
+    // the closed form expected value, derived from the online probability
+    Expected,
+
+    // each attestation is included by the first online proposer after its slot
+    Sampled,
+
+    // the delay is sampled from the given weights of the delays 1, 2, 3, ...
+    Empirical(Vec<f64>),
+}
+
+// a period of lower participation, e.g. a client bug or a network split
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outage {
+    // both included
+    pub from_epoch: i32,
+    pub to_epoch: i32,
+
+    // replaces the online probability of the config meanwhile
+    pub probability_online: f32,
+}
+
+// the income of the fee recipient of the proposer of a block, priority fees
+//   and MEV, in Gwei. It is not part of the beacon balances.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionRewards {
+    None,
+
+    // the same value for every block
+    Fixed(u64),
+
+    // sampled from a log-normal distribution of the given median and sigma
+    LogNormal(f64, f64),
+
+    // the values of historical blocks, replayed in order, from the start
+    //   once they run out
+    Replay(Vec<u64>),
+}
+
+impl ExecutionRewards {
+    // of a block, for the expected values of the analytic engine
+    pub fn get_mean(&self) -> f64 {
+        match self {
+            ExecutionRewards::None => 0.0,
+            ExecutionRewards::Fixed(value) => *value as f64,
+            ExecutionRewards::LogNormal(median, sigma) => median * (sigma * sigma / 2.0).exp(),
+            ExecutionRewards::Replay(values) => {
+                values.iter().sum::<u64>() as f64 / values.len() as f64
+            }
+        }
+    }
+
+    // parses lines of the form "ETH", or with more fields, e.g. "slot,ETH",
+    //   the value being the last one. A first line with no value is a header.
+    pub fn parse_csv(contents: &str) -> Result<ExecutionRewards, String> {
+        let mut values = vec![];
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let eth: Option<f64> = line.rsplit(',').next().and_then(|f| f.trim().parse().ok());
+            match eth {
+                Some(eth) if eth >= 0.0 => values.push((eth * 1_000_000_000.0).round() as u64),
+                None if line_number == 0 => continue,
+                _ => {
+                    return Err(format!(
+                        "invalid block value at line {}: {}",
+                        line_number + 1,
+                        line
+                    ))
+                }
+            }
+        }
+
+        if values.is_empty() {
+            return Err("execution_rewards_file has no block values".to_string());
+        }
+
+        Ok(ExecutionRewards::Replay(values))
+    }
+}
+
+// the rewards and penalties of which fork of the spec
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fork {
+    // base rewards split in source, target, head and inclusion delay
+    Phase0,
+
+    // weighted participation flags, set by timely attestations
+    Altair,
+}
+
+// how the validator set is represented during the simulation
+#[derive(Debug, Clone, PartialEq)]
+pub enum Engine {
+    // every validator is simulated on its own
+    Validators,
+
+    // validators in the same state are grouped in cohorts, tracked by count
+    //   and evolved with binomial sampling, for huge validator sets
+    Cohorts,
+
+    // no randomness: every epoch gets the expected deltas of the validators
+    Analytic,
+}
+
+#[derive(Debug, Clone)]
 pub struct Config {
     // what kind of reports are we producing here?
     pub printing_output: String,
@@ -22,105 +329,496 @@ pub struct Config {
     // how many epochs we want to run?
     pub epochs: i32,
 
+    // how many independent runs, with consecutive seeds, for a Monte Carlo report
+    pub runs: u64,
+
     // how much ETH we want to start with?
     pub total_at_stake_initial: u64,
 
+    // how much ETH is waiting in the activation queue at the start?
+    pub total_at_stake_queued: u64,
+
+    // how many new validators deposit at each epoch, and the deposits
+    //   (new validators and top-ups) scheduled for given epochs
+    pub deposits_per_epoch: u64,
+    pub deposit_schedule: Vec<Deposit>,
+
     // probabilities of any validator
     pub probability_online: f32,
     pub probability_honest: f32,
+    pub probability_slashed: f32,
+
+    // probabilities of a matching target given a matching source, and of a
+    //   matching head given a matching target
+    pub probability_target: f32,
+    pub probability_head: f32,
+
+    pub dishonest_behaviour: DishonestBehaviour,
+
+    // (epoch, number of validators) to be slashed on top of the probability
+    pub slashing_schedule: Vec<(i32, u64)>,
+
+    pub exit_policy: ExitPolicy,
+
+    pub outages: Vec<Outage>,
+
+    pub inclusion_delay: InclusionDelay,
+
+    pub execution_rewards: ExecutionRewards,
+
+    pub spec: SpecConstants,
+
+    pub fork: Fork,
+
+    pub engine: Engine,
+
+    // width in Gwei of the balance buckets merging cohorts together
+    pub cohort_bucket: u64,
+
+    // of the random number generator, so a run can be reproduced
+    pub seed: u64,
 
     // pre-computation
     pub exp_value_inclusion_prob: f32,
+    pub inclusion_delay_distribution: Option<WeightedIndex<f64>>,
+}
+
+impl Default for Config {
+    // a validator set of 500,000 ETH, with the seed fixed so any default run
+    //   is reproducible
+    fn default() -> Config {
+        Config {
+            printing_output: "epoch".to_string(),
+            // ideal default: 81_125 = (60 * 60 * 24 * 365)/(12 * 32)
+            // current default 10
+            epochs: 10,
+            runs: 1,
+            total_at_stake_initial: 500_000 * 1_000_000_000,
+            total_at_stake_queued: 0,
+            deposits_per_epoch: 0,
+            deposit_schedule: vec![],
+            probability_online: 0.99,
+            probability_honest: 1.0,
+            probability_slashed: 0.0,
+            probability_target: 1.0,
+            probability_head: 1.0,
+            dishonest_behaviour: DishonestBehaviour::WrongSource,
+            slashing_schedule: vec![],
+            exit_policy: ExitPolicy::None,
+            outages: vec![],
+            inclusion_delay: InclusionDelay::Expected,
+            execution_rewards: ExecutionRewards::None,
+            spec: SpecConstants::mainnet(),
+            fork: Fork::Phase0,
+            engine: Engine::Validators,
+            cohort_bucket: 1_000_000,
+            seed: 0,
+            exp_value_inclusion_prob: Config::get_exp_value_inclusion_prob(0.99),
+            inclusion_delay_distribution: None,
+        }
+    }
 }
 
 impl Config {
-    pub fn new() -> Config {
-        // parse command line options
-        let matches = App::new("Eth2 Reward Simulator")
-            .arg(
-                Arg::with_name("initial_stake")
-                    .short("i")
-                    .long("initial_stake")
-                    .value_name("ETH")
-                    .help("Your initial stake in ETH"),
-            )
-            .arg(
-                Arg::with_name("epochs")
-                    .short("e")
-                    .long("epochs")
-                    .value_name("t")
-                    .help("Epochs to run"),
-            )
-            .arg(
-                Arg::with_name("probability_online")
-                    .short("p")
-                    .long("probability_online")
-                    .value_name("p")
-                    .help("A value in [0,1]"),
-            )
-            .arg(
-                Arg::with_name("printing_output")
-                    .short("r")
-                    .long("printing_output")
-                    .value_name("option")
-                    .help("Type of report (epoch, monthly)"),
-            )
-            .get_matches();
-
-        let initial_stake = matches.value_of("initial_stake").unwrap_or("500000");
-        let initial_stake: u64 = match initial_stake.trim().parse() {
-            Ok(num) => num,
-            Err(_) => 500_000,
-        };
-        if initial_stake < 500_000 {
-            panic!("initial_stake should be equal or greater than 500000")
-        }
-
-        // ideal default: 81_125 = (60 * 60 * 24 * 365)/(12 * 32)
-        // current default 10
-        let epochs = matches.value_of("epochs").unwrap_or("10");
-        let epochs: i32 = match epochs.trim().parse() {
-            Ok(num) => num,
-            Err(_) => 10,
-        };
-        if epochs < 1 {
-            panic!("epoch should be a positive integer")
-        }
-
-        let probability_online = matches.value_of("probability_online").unwrap_or("0.99");
-        let probability_online: f32 = match probability_online.trim().parse() {
-            Ok(num) => num,
-            Err(_) => 0.99,
-        };
-        if probability_online < 0.0 || probability_online > 1.0 {
-            panic!("probability_online should be in the interval [0,1]");
-        }
-
-        let printing_output = matches.value_of("printing_output").unwrap_or("epoch");
-        if printing_output != "epoch" && printing_output != "monthly" {
-            panic!("printing_output only supports 'epoch' or 'monthly'");
-        }
-
-        // stick to 1.0 for now
-        let probability_honest: f32 = 1.0;
+    // checks the values set on a config, and fills the pre-computations,
+    //   or returns what is wrong with it. To be called once every field
+    //   is set, e.g.
+    //   Config { epochs: 100, ..Config::default() }.build()
+    pub fn build(mut self) -> Result<Config, String> {
+        if self.total_at_stake_initial < 500_000 * 1_000_000_000 {
+            return Err("initial_stake should be equal or greater than 500000".to_string());
+        }
+
+        if self.epochs < 1 {
+            return Err("epoch should be a positive integer".to_string());
+        }
+
+        if self.runs < 1 {
+            return Err("runs should be a positive integer".to_string());
+        }
+
+        let probabilities = [
+            ("probability_online", self.probability_online),
+            ("probability_honest", self.probability_honest),
+            ("probability_target", self.probability_target),
+            ("probability_head", self.probability_head),
+            ("probability_slashed", self.probability_slashed),
+        ];
+        for (name, probability) in probabilities.iter() {
+            if !(0.0..=1.0).contains(probability) {
+                return Err(format!("{} should be in the interval [0,1]", name));
+            }
+        }
+
+        for outage in self.outages.iter() {
+            if outage.from_epoch > outage.to_epoch {
+                return Err("outages should end after they start".to_string());
+            }
+            if !(0.0..=1.0).contains(&outage.probability_online) {
+                return Err(
+                    "the probability_online of outages should be in the interval [0,1]".to_string(),
+                );
+            }
+        }
+
+        // a batch before SHARD_COMMITTEE_PERIOD is pushed back to it
+        if let ExitPolicy::Batch(epoch, _) = self.exit_policy {
+            if epoch < 0 {
+                return Err("the epoch of a batch exit should not be negative".to_string());
+            }
+            let batch_epoch = std::cmp::max(epoch as u64, self.spec.shard_committee_period);
+            if batch_epoch >= self.epochs as u64 {
+                return Err(format!(
+                    "the batch exit happens at epoch {}, after the last epoch",
+                    batch_epoch
+                ));
+            }
+        }
+
+        if self.printing_output != "epoch" && self.printing_output != "monthly" {
+            return Err("printing_output only supports 'epoch' or 'monthly'".to_string());
+        }
+
+        if self.cohort_bucket < 1 {
+            return Err("cohort_bucket should be a positive integer".to_string());
+        }
+
+        // the constants are divisors, and a delay is at most an epoch
+        let spec = &self.spec;
+        if spec.effective_balance_increment == 0
+            || spec.base_rewards_per_epoch == 0
+            || spec.proposer_reward_quotient == 0
+            || spec.slots_per_epoch == 0
+            || spec.inactivity_penalty_quotient == 0
+            || spec.inactivity_penalty_quotient_altair == 0
+            || spec.inactivity_score_bias == 0
+            || spec.sync_committee_size == 0
+            || spec.epochs_per_sync_committee_period == 0
+            || spec.min_slashing_penalty_quotient == 0
+            || spec.min_slashing_penalty_quotient_altair == 0
+            || spec.churn_limit_quotient == 0
+            || spec.epochs_per_slashings_vector == 0
+        {
+            return Err(
+                "spec constants other than balances and factors should be positive".to_string(),
+            );
+        }
+        if spec.weight_denominator <= spec.proposer_weight {
+            return Err("WEIGHT_DENOMINATOR should be greater than PROPOSER_WEIGHT".to_string());
+        }
+        if spec.max_effective_balance < spec.effective_balance_increment {
+            return Err(
+                "MAX_EFFECTIVE_BALANCE should be at least EFFECTIVE_BALANCE_INCREMENT".to_string(),
+            );
+        }
+        if let InclusionDelay::Empirical(weights) = &self.inclusion_delay {
+            if weights.len() as u64 > spec.slots_per_epoch {
+                return Err("inclusion_delay weights should be up to SLOTS_PER_EPOCH".to_string());
+            }
+        }
+
+        self.check_deposit_schedule()?;
+        self.check_engine_support()?;
 
         // pre-computation
-        let exp_value_inclusion_prob = Config::get_exp_value_inclusion_prob(probability_online);
+        self.exp_value_inclusion_prob =
+            Config::get_exp_value_inclusion_prob(self.probability_online);
+        self.inclusion_delay_distribution = match &self.inclusion_delay {
+            InclusionDelay::Empirical(weights) => Some(
+                WeightedIndex::new(weights)
+                    .map_err(|_| "inclusion_delay weights should not all be 0".to_string())?,
+            ),
+            _ => None,
+        };
 
-        Config {
-            printing_output: printing_output.to_string(),
-            epochs: epochs,
-            total_at_stake_initial: initial_stake * 1_000_000_000,
-            probability_online: probability_online,
-            probability_honest: probability_honest,
-            exp_value_inclusion_prob: exp_value_inclusion_prob,
+        Ok(self)
+    }
+
+    // the outage going on at the given epoch, if any
+    pub fn get_outage(&self, epoch: i32) -> Option<&Outage> {
+        self.outages
+            .iter()
+            .find(|o| o.from_epoch <= epoch && epoch <= o.to_epoch)
+    }
+
+    // size of the registry at the start, genesis and queued validators
+    pub fn get_initial_validators(&self) -> usize {
+        ((self.total_at_stake_initial + self.total_at_stake_queued)
+            / self.spec.max_effective_balance) as usize
+    }
+
+    // sets the online probability for the epochs to come, along with its
+    //   pre-computation
+    pub fn set_probability_online(&mut self, probability_online: f32) {
+        self.probability_online = probability_online;
+        self.exp_value_inclusion_prob = Config::get_exp_value_inclusion_prob(probability_online);
+    }
+
+    // probabilities of a validator having matched each vote, unconditionally
+    pub fn get_probability_source(&self) -> f32 {
+        self.probability_online * self.get_probability_not_failing(DishonestBehaviour::WrongSource)
+    }
+
+    pub fn get_probability_target(&self) -> f32 {
+        self.get_probability_source()
+            * self.probability_target
+            * self.get_probability_not_failing(DishonestBehaviour::WrongTarget)
+    }
+
+    pub fn get_probability_head(&self) -> f32 {
+        self.get_probability_target()
+            * self.probability_head
+            * self.get_probability_not_failing(DishonestBehaviour::WrongHead)
+    }
+
+    // probability of an included attestation having a delay of at most
+    //   max_delay. Phase 0 pays for late attestations, Altair does not.
+    pub fn get_probability_timely(&self, max_delay: u64) -> f64 {
+        if self.fork == Fork::Phase0 {
+            return 1.0;
+        }
+
+        match &self.inclusion_delay {
+            // the attestations are assumed to be included in the next slot
+            InclusionDelay::Expected => 1.0,
+            InclusionDelay::Sampled => {
+                1.0 - (1.0 - self.probability_online as f64).powi(max_delay as i32)
+            }
+            // the attestation waits for the first online proposer from the
+            //   slot its delay leads to
+            InclusionDelay::Empirical(weights) => {
+                let probability_offline = 1.0 - self.probability_online as f64;
+                let timely: f64 = weights
+                    .iter()
+                    .take(max_delay as usize)
+                    .enumerate()
+                    .map(|(index, w)| {
+                        w * (1.0 - probability_offline.powi(max_delay as i32 - index as i32))
+                    })
+                    .sum();
+                timely / weights.iter().sum::<f64>()
+            }
         }
     }
 
-    fn get_exp_value_inclusion_prob(p: f32) -> f32 {
-        if p == 0.0 {
-            p
-        } else if p == 1.0 {
+    // only dishonest validators with the given behaviour fail the vote
+    fn get_probability_not_failing(&self, behaviour: DishonestBehaviour) -> f32 {
+        if self.dishonest_behaviour == behaviour {
+            self.probability_honest
+        } else {
+            1.0
+        }
+    }
+
+    pub fn parse_engine(engine: &str) -> Result<Engine, String> {
+        match engine.trim() {
+            "validators" => Ok(Engine::Validators),
+            "cohorts" => Ok(Engine::Cohorts),
+            "analytic" => Ok(Engine::Analytic),
+            _ => Err("engine only supports 'validators', 'cohorts' or 'analytic'".to_string()),
+        }
+    }
+
+    pub fn parse_fork(fork: &str) -> Result<Fork, String> {
+        match fork.trim() {
+            "phase0" => Ok(Fork::Phase0),
+            "altair" => Ok(Fork::Altair),
+            _ => Err("fork only supports 'phase0' or 'altair'".to_string()),
+        }
+    }
+
+    // a top-up has to target one of the validators at the start, or one
+    //   created by a deposit processed before it. The deposits are processed
+    //   by epoch, those of deposits_per_epoch first, then those of the
+    //   schedule in its order.
+    fn check_deposit_schedule(&self) -> Result<(), String> {
+        for (position, deposit) in self.deposit_schedule.iter().enumerate() {
+            let index = match deposit.validator_index {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let created_per_epoch =
+                self.deposits_per_epoch as usize * std::cmp::max(deposit.epoch + 1, 0) as usize;
+            let created_by_schedule = self
+                .deposit_schedule
+                .iter()
+                .enumerate()
+                .filter(|(other_position, other)| {
+                    other.validator_index.is_none()
+                        && (other.epoch < deposit.epoch
+                            || (other.epoch == deposit.epoch && *other_position < position))
+                })
+                .count();
+
+            if index >= self.get_initial_validators() + created_per_epoch + created_by_schedule {
+                return Err(format!(
+                    "unknown validator index {} in the deposit at epoch {}",
+                    index, deposit.epoch
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // cohorts only hold unslashed validators with the expected inclusion
+    //   delay, which leave the registry by ejection only, so the other options
+    //   changing the registry are not supported. The same goes for the
+    //   analytic engine, which works on cohorts too.
+    fn check_engine_support(&self) -> Result<(), String> {
+        if self.engine == Engine::Validators {
+            return Ok(());
+        }
+
+        if self.total_at_stake_queued > 0
+            || self.deposits_per_epoch > 0
+            || !self.deposit_schedule.is_empty()
+        {
+            return Err(format!(
+                "the {:?} engine does not support deposits",
+                self.engine
+            ));
+        }
+        if self.probability_slashed > 0.0
+            || !self.slashing_schedule.is_empty()
+            || (self.probability_honest < 1.0
+                && self.dishonest_behaviour == DishonestBehaviour::Equivocation)
+        {
+            return Err(format!(
+                "the {:?} engine does not support slashings",
+                self.engine
+            ));
+        }
+        if self.exit_policy != ExitPolicy::None {
+            return Err(format!(
+                "the {:?} engine does not support voluntary exits",
+                self.engine
+            ));
+        }
+        if self.inclusion_delay != InclusionDelay::Expected {
+            return Err(format!(
+                "the {:?} engine only supports the expected inclusion delay",
+                self.engine
+            ));
+        }
+
+        Ok(())
+    }
+
+    // parses lists of the form "epoch:count,epoch:count"
+    pub fn parse_schedule(schedule: &str) -> Result<Vec<(i32, u64)>, String> {
+        schedule
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(|item| {
+                let mut parts = item.trim().split(':');
+                let epoch = parts.next().and_then(|p| p.trim().parse().ok());
+                let count = parts.next().and_then(|p| p.trim().parse().ok());
+
+                match (epoch, count, parts.next()) {
+                    (Some(epoch), Some(count), None) => Ok((epoch, count)),
+                    _ => Err("schedule items should be of the form epoch:count".to_string()),
+                }
+            })
+            .collect()
+    }
+
+    pub fn parse_exit_policy(exit_policy: &str) -> Result<ExitPolicy, String> {
+        let parts: Vec<&str> = exit_policy.trim().split(':').map(|p| p.trim()).collect();
+
+        let policy = match parts.as_slice() {
+            ["none"] => Some(ExitPolicy::None),
+            ["rate", n] => n.parse().ok().map(ExitPolicy::FixedRate),
+            ["apr", rate, n] => match (rate.parse(), n.parse()) {
+                (Ok(rate), Ok(n)) => Some(ExitPolicy::AprBelow(rate, n)),
+                _ => None,
+            },
+            ["batch", epoch, n] => match (epoch.parse(), n.parse()) {
+                (Ok(epoch), Ok(n)) => Some(ExitPolicy::Batch(epoch, n)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        policy.ok_or_else(|| {
+            "exit_policy only supports none, rate:<n>, apr:<rate>:<n> or batch:<epoch>:<n>"
+                .to_string()
+        })
+    }
+
+    pub fn parse_dishonest_behaviour(
+        dishonest_behaviour: &str,
+    ) -> Result<DishonestBehaviour, String> {
+        match dishonest_behaviour.trim() {
+            "wrong_source" => Ok(DishonestBehaviour::WrongSource),
+            "wrong_target" => Ok(DishonestBehaviour::WrongTarget),
+            "wrong_head" => Ok(DishonestBehaviour::WrongHead),
+            "equivocation" => Ok(DishonestBehaviour::Equivocation),
+            _ => Err(
+                "dishonest_behaviour only supports wrong_source, wrong_target, wrong_head or equivocation"
+                    .to_string(),
+            ),
+        }
+    }
+
+    pub fn parse_inclusion_delay(inclusion_delay: &str) -> Result<InclusionDelay, String> {
+        let inclusion_delay = inclusion_delay.trim();
+
+        let model = match inclusion_delay {
+            "expected" => Some(InclusionDelay::Expected),
+            "sampled" => Some(InclusionDelay::Sampled),
+            _ if inclusion_delay.starts_with("empirical:") => {
+                let weights: Vec<Option<f64>> = inclusion_delay["empirical:".len()..]
+                    .split(',')
+                    .map(|w| w.trim().parse().ok().filter(|w: &f64| *w >= 0.0))
+                    .collect();
+
+                let weights: Option<Vec<f64>> = weights.into_iter().collect();
+
+                match weights {
+                    Some(weights) if weights.iter().sum::<f64>() > 0.0 => {
+                        Some(InclusionDelay::Empirical(weights))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        model.ok_or_else(|| {
+            "inclusion_delay only supports expected, sampled or empirical:<w1>,<w2>,... (up to SLOTS_PER_EPOCH weights)"
+                .to_string()
+        })
+    }
+
+    pub fn parse_execution_rewards(execution_rewards: &str) -> Result<ExecutionRewards, String> {
+        let parts: Vec<&str> = execution_rewards
+            .trim()
+            .split(':')
+            .map(|p| p.trim())
+            .collect();
+
+        let model = match parts.as_slice() {
+            ["none"] => Some(ExecutionRewards::None),
+            ["fixed", value] => value.parse().ok().map(ExecutionRewards::Fixed),
+            ["lognormal", median, sigma] => match (median.parse(), sigma.parse()) {
+                (Ok(median), Ok(sigma)) if median > 0.0 && sigma >= 0.0 => {
+                    Some(ExecutionRewards::LogNormal(median, sigma))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        model.ok_or_else(|| {
+            "execution_rewards only supports none, fixed:<Gwei> or lognormal:<median Gwei>:<sigma>"
+                .to_string()
+        })
+    }
+
+    pub fn get_exp_value_inclusion_prob(p: f32) -> f32 {
+        if p == 0.0 || p == 1.0 {
             p
         } else {
             p * p.ln() / (p - 1.00)
@@ -156,6 +854,345 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_schedule() {
+        assert_eq!(Ok(Vec::<(i32, u64)>::new()), Config::parse_schedule(""));
+        assert_eq!(Ok(vec![(10, 1)]), Config::parse_schedule("10:1"));
+        assert_eq!(
+            Ok(vec![(10, 1), (200, 32)]),
+            Config::parse_schedule("10:1, 200:32")
+        );
+    }
+
+    #[test]
+    fn parse_schedule_malformed() {
+        assert!(Config::parse_schedule("10:1:3").is_err());
+    }
+
+    #[test]
+    fn parse_exit_policy() {
+        assert_eq!(Ok(ExitPolicy::None), Config::parse_exit_policy("none"));
+        assert_eq!(
+            Ok(ExitPolicy::FixedRate(4)),
+            Config::parse_exit_policy("rate:4")
+        );
+        assert_eq!(
+            Ok(ExitPolicy::AprBelow(0.05, 10)),
+            Config::parse_exit_policy("apr:0.05:10")
+        );
+        assert_eq!(
+            Ok(ExitPolicy::Batch(300, 1000)),
+            Config::parse_exit_policy("batch:300:1000")
+        );
+    }
+
+    #[test]
+    fn parse_exit_policy_malformed() {
+        assert!(Config::parse_exit_policy("rate:four").is_err());
+    }
+
+    #[test]
+    fn parse_dishonest_behaviour() {
+        assert_eq!(
+            Ok(DishonestBehaviour::WrongSource),
+            Config::parse_dishonest_behaviour("wrong_source")
+        );
+        assert_eq!(
+            Ok(DishonestBehaviour::WrongTarget),
+            Config::parse_dishonest_behaviour("wrong_target")
+        );
+        assert_eq!(
+            Ok(DishonestBehaviour::WrongHead),
+            Config::parse_dishonest_behaviour("wrong_head")
+        );
+        assert_eq!(
+            Ok(DishonestBehaviour::Equivocation),
+            Config::parse_dishonest_behaviour("equivocation")
+        );
+    }
+
+    #[test]
+    fn parse_dishonest_behaviour_malformed() {
+        assert!(Config::parse_dishonest_behaviour("lazy").is_err());
+    }
+
+    #[test]
+    fn parse_inclusion_delay() {
+        assert_eq!(
+            Ok(InclusionDelay::Expected),
+            Config::parse_inclusion_delay("expected")
+        );
+        assert_eq!(
+            Ok(InclusionDelay::Sampled),
+            Config::parse_inclusion_delay("sampled")
+        );
+        assert_eq!(
+            Ok(InclusionDelay::Empirical(vec![0.9, 0.08, 0.02])),
+            Config::parse_inclusion_delay("empirical:0.9,0.08,0.02")
+        );
+    }
+
+    #[test]
+    fn parse_inclusion_delay_malformed() {
+        assert!(Config::parse_inclusion_delay("empirical:0.9,-0.1").is_err());
+    }
+
+    #[test]
+    fn parse_execution_rewards() {
+        assert_eq!(
+            Ok(ExecutionRewards::None),
+            Config::parse_execution_rewards("none")
+        );
+        assert_eq!(
+            Ok(ExecutionRewards::Fixed(50_000_000)),
+            Config::parse_execution_rewards("fixed:50000000")
+        );
+        assert_eq!(
+            Ok(ExecutionRewards::LogNormal(40_000_000.0, 1.2)),
+            Config::parse_execution_rewards(" lognormal:40000000:1.2")
+        );
+    }
+
+    #[test]
+    fn parse_execution_rewards_malformed() {
+        assert!(Config::parse_execution_rewards("lognormal:0:1.2").is_err());
+    }
+
+    #[test]
+    fn execution_rewards_csv() {
+        let execution_rewards =
+            ExecutionRewards::parse_csv("slot,value\n# a comment\n100,0.05\n\n101,0.0123\n0.1\n")
+                .unwrap();
+
+        assert_eq!(
+            ExecutionRewards::Replay(vec![50_000_000, 12_300_000, 100_000_000]),
+            execution_rewards
+        );
+        assert_eq!(54_100_000.0, execution_rewards.get_mean());
+        assert_eq!(
+            (2.0_f64).exp(),
+            ExecutionRewards::LogNormal(1.0, 2.0).get_mean()
+        );
+    }
+
+    #[test]
+    fn execution_rewards_csv_malformed() {
+        assert!(ExecutionRewards::parse_csv("0.05\n100,-0.1\n").is_err());
+    }
+
+    #[test]
+    fn parse_engine() {
+        assert_eq!(Ok(Engine::Validators), Config::parse_engine("validators"));
+        assert_eq!(Ok(Engine::Cohorts), Config::parse_engine(" cohorts"));
+        assert_eq!(Ok(Engine::Analytic), Config::parse_engine("analytic"));
+        assert!(Config::parse_engine("buckets").is_err());
+    }
+
+    #[test]
+    fn parse_fork() {
+        assert_eq!(Ok(Fork::Phase0), Config::parse_fork("phase0"));
+        assert_eq!(Ok(Fork::Altair), Config::parse_fork(" altair"));
+        assert!(Config::parse_fork("bellatrix").is_err());
+    }
+
+    #[test]
+    fn get_probability_timely() {
+        let config = Config {
+            probability_online: 0.5,
+            inclusion_delay: InclusionDelay::Sampled,
+            ..Config::default()
+        };
+        assert_eq!(1.0, config.get_probability_timely(1));
+
+        let config = Config {
+            fork: Fork::Altair,
+            ..config
+        };
+        assert_eq!(0.5, config.get_probability_timely(1));
+        assert_eq!(0.875, config.get_probability_timely(3));
+
+        let config = Config {
+            inclusion_delay: Config::parse_inclusion_delay("empirical:0.5,0.25,0.25").unwrap(),
+            ..config
+        };
+        // the first delays wait for an online proposer
+        assert_eq!(0.5, config.get_probability_timely(2));
+        assert!(config.get_probability_timely(32) > 0.999);
+    }
+
+    fn top_up(epoch: i32, validator_index: usize) -> Deposit {
+        Deposit {
+            epoch,
+            amount: 1_000_000_000,
+            validator_index: Some(validator_index),
+        }
+    }
+
+    fn new_validator(epoch: i32) -> Deposit {
+        Deposit {
+            epoch,
+            amount: 32_000_000_000,
+            validator_index: None,
+        }
+    }
+
+    #[test]
+    fn build_top_up_unknown_validator_index() {
+        let config = Config {
+            deposit_schedule: vec![top_up(7, 15625)],
+            ..Config::default()
+        };
+
+        assert_eq!(
+            Some("unknown validator index 15625 in the deposit at epoch 7".to_string()),
+            config.build().err()
+        );
+    }
+
+    #[test]
+    fn build_top_up_of_new_validator() {
+        // the validator 15625 is created at epoch 3, before the top-up
+        let config = Config {
+            deposit_schedule: vec![top_up(5, 15625), new_validator(3)],
+            ..Config::default()
+        };
+        assert!(config.build().is_ok());
+
+        // but only at epoch 5 here
+        let config = Config {
+            deposit_schedule: vec![new_validator(5), top_up(3, 15625)],
+            ..Config::default()
+        };
+        assert!(config.build().is_err());
+
+        // or by the deposits of each epoch, up to that of the top-up
+        let config = Config {
+            deposits_per_epoch: 2,
+            deposit_schedule: vec![top_up(0, 15626), top_up(1, 15628)],
+            ..Config::default()
+        };
+        assert!(config.build().is_ok());
+    }
+
+    #[test]
+    fn build_top_up_of_queued_validator() {
+        // the queued validators are in the registry from the start
+        let config = Config {
+            total_at_stake_queued: 64_000_000_000,
+            deposit_schedule: vec![top_up(0, 15626)],
+            ..Config::default()
+        };
+
+        assert!(config.build().is_ok());
+    }
+
+    #[test]
+    fn build_batch_exit_out_of_the_run() {
+        let config = Config {
+            epochs: 1000,
+            exit_policy: ExitPolicy::Batch(-1, 100),
+            ..Config::default()
+        };
+        assert_eq!(
+            Some("the epoch of a batch exit should not be negative".to_string()),
+            config.build().err()
+        );
+
+        let config = Config {
+            epochs: 1000,
+            exit_policy: ExitPolicy::Batch(1000, 100),
+            ..Config::default()
+        };
+        assert!(config.build().is_err());
+
+        // pushed back to SHARD_COMMITTEE_PERIOD
+        let config = Config {
+            epochs: 200,
+            exit_policy: ExitPolicy::Batch(100, 100),
+            ..Config::default()
+        };
+        assert_eq!(
+            Some("the batch exit happens at epoch 256, after the last epoch".to_string()),
+            config.build().err()
+        );
+
+        let config = Config {
+            epochs: 1000,
+            exit_policy: ExitPolicy::Batch(999, 100),
+            ..Config::default()
+        };
+        assert!(config.build().is_ok());
+    }
+
+    #[test]
+    fn check_engine_support_exits() {
+        let config = Config {
+            engine: Engine::Cohorts,
+            exit_policy: ExitPolicy::FixedRate(4),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            Err("the Cohorts engine does not support voluntary exits".to_string()),
+            config.check_engine_support()
+        );
+    }
+
+    #[test]
+    fn spec_presets() {
+        assert_eq!(
+            Ok(SpecConstants::mainnet()),
+            SpecConstants::from_preset("mainnet")
+        );
+        assert_eq!(
+            8,
+            SpecConstants::from_preset("minimal")
+                .unwrap()
+                .slots_per_epoch
+        );
+        assert!(SpecConstants::from_preset("goerli").is_err());
+        assert_eq!(
+            SpecConstants::mainnet().base_reward_factor,
+            SpecConstants::minimal().base_reward_factor
+        );
+        assert_eq!(64, SpecConstants::minimal().epochs_per_slashings_vector);
+        assert_eq!(64, SpecConstants::minimal().shard_committee_period);
+    }
+
+    #[test]
+    fn spec_overrides() {
+        let mut spec = SpecConstants::mainnet();
+        for (name, value) in
+            SpecConstants::parse_overrides("BASE_REWARD_FACTOR=128, slots_per_epoch=16").unwrap()
+        {
+            spec.set(&name, value).unwrap();
+        }
+
+        assert_eq!(128, spec.base_reward_factor);
+        assert_eq!(16, spec.slots_per_epoch);
+    }
+
+    #[test]
+    fn spec_overrides_unknown_constant() {
+        assert!(SpecConstants::mainnet().set("BASE_REWARD", 128).is_err());
+    }
+
+    #[test]
+    fn spec_overrides_malformed() {
+        assert!(SpecConstants::parse_overrides("BASE_REWARD_FACTOR:128").is_err());
+    }
+
+    #[test]
+    fn build_inclusion_delay_longer_than_an_epoch() {
+        let config = Config {
+            spec: SpecConstants::minimal(),
+            inclusion_delay: Config::parse_inclusion_delay("empirical:1,1,1,1,1,1,1,1,1").unwrap(),
+            ..Config::default()
+        };
+
+        assert!(config.build().is_err());
+    }
+
     fn truncate_two(number: f32) -> f32 {
         (number * 100.0).floor() / 100.0
     }
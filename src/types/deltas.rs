@@ -6,30 +6,102 @@
 
 use std::fmt;
 
+#[derive(Default)]
 pub struct Deltas {
-    pub head_ffg_reward: u64,
-    pub head_ffg_penalty: u64,
+    pub source_reward: u64,
+    pub source_penalty: u64,
+    pub target_reward: u64,
+    pub target_penalty: u64,
+    pub head_reward: u64,
+    pub head_penalty: u64,
     pub proposer_reward: u64,
     pub attester_reward: u64,
+    pub inactivity_penalty: u64,
+    pub slashing_penalty: u64,
+    pub whistleblower_reward: u64,
+    pub sync_reward: u64,
+    pub sync_penalty: u64,
 }
 
 impl Deltas {
     pub fn new() -> Deltas {
         Deltas {
-            head_ffg_reward: 0,
-            head_ffg_penalty: 0,
+            source_reward: 0,
+            source_penalty: 0,
+            target_reward: 0,
+            target_penalty: 0,
+            head_reward: 0,
+            head_penalty: 0,
             proposer_reward: 0,
             attester_reward: 0,
+            inactivity_penalty: 0,
+            slashing_penalty: 0,
+            whistleblower_reward: 0,
+            sync_reward: 0,
+            sync_penalty: 0,
         }
     }
+
+    // sums the deltas of another validator, for the aggregates of the epoch
+    pub fn add(&mut self, other: &Deltas) {
+        self.add_times(other, 1);
+    }
+
+    // sums the deltas of a number of validators that got the same ones
+    pub fn add_times(&mut self, other: &Deltas, count: u64) {
+        self.source_reward += other.source_reward * count;
+        self.source_penalty += other.source_penalty * count;
+        self.target_reward += other.target_reward * count;
+        self.target_penalty += other.target_penalty * count;
+        self.head_reward += other.head_reward * count;
+        self.head_penalty += other.head_penalty * count;
+        self.proposer_reward += other.proposer_reward * count;
+        self.attester_reward += other.attester_reward * count;
+        self.inactivity_penalty += other.inactivity_penalty * count;
+        self.slashing_penalty += other.slashing_penalty * count;
+        self.whistleblower_reward += other.whistleblower_reward * count;
+        self.sync_reward += other.sync_reward * count;
+        self.sync_penalty += other.sync_penalty * count;
+    }
+
+    // sums the deltas of an expected, fractional, number of validators
+    pub fn add_scaled(&mut self, other: &Deltas, count: f64) {
+        let scale = |delta: u64| (delta as f64 * count).round() as u64;
+
+        self.source_reward += scale(other.source_reward);
+        self.source_penalty += scale(other.source_penalty);
+        self.target_reward += scale(other.target_reward);
+        self.target_penalty += scale(other.target_penalty);
+        self.head_reward += scale(other.head_reward);
+        self.head_penalty += scale(other.head_penalty);
+        self.proposer_reward += scale(other.proposer_reward);
+        self.attester_reward += scale(other.attester_reward);
+        self.inactivity_penalty += scale(other.inactivity_penalty);
+        self.slashing_penalty += scale(other.slashing_penalty);
+        self.whistleblower_reward += scale(other.whistleblower_reward);
+        self.sync_reward += scale(other.sync_reward);
+        self.sync_penalty += scale(other.sync_penalty);
+    }
 }
 
 impl fmt::Display for Deltas {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{};{};{};{};",
-            self.head_ffg_reward, self.head_ffg_penalty, self.proposer_reward, self.attester_reward,
+            "{};{};{};{};{};{};{};{};{};{};{};{};{};",
+            self.source_reward,
+            self.source_penalty,
+            self.target_reward,
+            self.target_penalty,
+            self.head_reward,
+            self.head_penalty,
+            self.proposer_reward,
+            self.attester_reward,
+            self.inactivity_penalty,
+            self.slashing_penalty,
+            self.whistleblower_reward,
+            self.sync_reward,
+            self.sync_penalty,
         )
     }
 }
@@ -0,0 +1,124 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// A deposit to the beacon chain, new validator or top-up
+//
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deposit {
+    // the epoch at which the deposit reaches the beacon chain
+    pub epoch: i32,
+    pub amount: u64,
+
+    // the validator to top up. a new validator is created otherwise
+    pub validator_index: Option<usize>,
+}
+
+impl Deposit {
+    // parses lines of the form "epoch,ETH" or "epoch,ETH,validator_index".
+    //   the ETH of a line without validator index is split into new
+    //   validators of MAX_EFFECTIVE_BALANCE, plus one with the remainder.
+    //   the validator indices of the top-ups are checked by `Config::build`.
+    pub fn parse_csv(contents: &str, max_effective_balance: u64) -> Result<Vec<Deposit>, String> {
+        let mut deposits = vec![];
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("epoch") {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            let epoch: Option<i32> = fields.first().and_then(|f| f.parse().ok());
+            let eth: Option<f64> = fields.get(1).and_then(|f| f.parse().ok());
+            let validator_index: Option<usize> = fields.get(2).and_then(|f| f.parse().ok());
+
+            let (epoch, amount) = match (epoch, eth, fields.len()) {
+                (Some(epoch), Some(eth), 2) | (Some(epoch), Some(eth), 3) if eth > 0.0 => {
+                    (epoch, (eth * 1_000_000_000.0) as u64)
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid deposit at line {}: {}",
+                        line_number + 1,
+                        line
+                    ))
+                }
+            };
+
+            if fields.len() == 3 {
+                if validator_index.is_none() {
+                    return Err(format!(
+                        "invalid deposit at line {}: {}",
+                        line_number + 1,
+                        line
+                    ));
+                }
+
+                deposits.push(Deposit {
+                    epoch,
+                    amount,
+                    validator_index,
+                });
+                continue;
+            }
+
+            for _ in 0..amount / max_effective_balance {
+                deposits.push(Deposit {
+                    epoch,
+                    amount: max_effective_balance,
+                    validator_index: None,
+                });
+            }
+            if amount % max_effective_balance > 0 {
+                deposits.push(Deposit {
+                    epoch,
+                    amount: amount % max_effective_balance,
+                    validator_index: None,
+                });
+            }
+        }
+
+        Ok(deposits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_csv() {
+        let contents = "epoch,ETH\n\n# new validators\n3,64\n5,40\n\n# top-up\n7,1.5,42\n";
+        let deposits = Deposit::parse_csv(contents, 32_000_000_000).unwrap();
+
+        let new_validator = |epoch, amount| Deposit {
+            epoch,
+            amount,
+            validator_index: None,
+        };
+
+        assert_eq!(
+            vec![
+                new_validator(3, 32_000_000_000),
+                new_validator(3, 32_000_000_000),
+                new_validator(5, 32_000_000_000),
+                new_validator(5, 8_000_000_000),
+                Deposit {
+                    epoch: 7,
+                    amount: 1_500_000_000,
+                    validator_index: Some(42),
+                },
+            ],
+            deposits
+        );
+    }
+
+    #[test]
+    fn parse_csv_malformed() {
+        assert_eq!(
+            Err("invalid deposit at line 1: 3,64,alice".to_string()),
+            Deposit::parse_csv("3,64,alice\n", 32_000_000_000)
+        );
+    }
+}
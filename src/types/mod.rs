@@ -4,14 +4,20 @@
 //
 ////////////////////////////////////////////////////////////////////////////////
 
+pub mod cohort;
 pub mod config;
 pub mod deltas;
+pub mod deposit;
 pub mod output;
+pub mod scenario;
 pub mod state;
 pub mod validator;
 
+pub use cohort::*;
 pub use config::*;
 pub use deltas::*;
+pub use deposit::*;
 pub use output::*;
+pub use scenario::*;
 pub use state::*;
 pub use validator::*;
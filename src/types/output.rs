@@ -4,11 +4,50 @@
 //
 ////////////////////////////////////////////////////////////////////////////////
 use super::*;
-use integer_sqrt::IntegerSquareRoot;
+use std::collections::BTreeMap;
 use std::time::Instant;
 
 const MONTHS_PER_YEAR: i32 = 12;
 
+pub const EPOCH_REPORT_HEADER: [&str; 35] = [
+    "epoch number",
+    "source rewards",
+    "source penalties",
+    "target rewards",
+    "target penalties",
+    "head rewards",
+    "head penalties",
+    "proposer rewards",
+    "attester rewards",
+    "inactivity penalties",
+    "slashing penalties",
+    "whistleblower rewards",
+    "sync committee rewards",
+    "sync committee penalties",
+    "execution rewards",
+    "total staked balance",
+    "total effective balance",
+    "max balance",
+    "min balance",
+    "total validators",
+    "total active validatos",
+    "epochs since finality",
+    "slashed validators",
+    "deposits",
+    "deposited balance",
+    "activated validators",
+    "pending validators",
+    "voluntary exits",
+    "exited validators",
+    "inactivity score p50",
+    "inactivity score p95",
+    "inactivity score p99",
+    "inactivity score max",
+    "validators with inactivity score",
+    "time μs",
+];
+
+#[derive(Default)]
 pub struct Output {
     pub rows: Vec<EpochReportRow>,
 }
@@ -17,7 +56,7 @@ impl Output {
     pub fn new() -> Output {
         let rows = vec![];
 
-        Output { rows: rows }
+        Output { rows }
     }
 
     pub fn push(&mut self, row: EpochReportRow) {
@@ -26,80 +65,124 @@ impl Output {
 
     pub fn print_epoch_report(&self, mode: &str) {
         if mode == "csv" {
-            println!(
-                "{},{},{},{},{},{},{},{},{},{},{},{}",
-                "epoch number".to_string(),
-                "FFG rewards".to_string(),
-                "FFG penalties".to_string(),
-                "proposer rewards".to_string(),
-                "attester rewards".to_string(),
-                "total staked balance".to_string(),
-                "total effective balance".to_string(),
-                "max balance".to_string(),
-                "min balance".to_string(),
-                "total validators".to_string(),
-                "total active validatos".to_string(),
-                "time μs".to_string(),
-            );
+            println!("{}", EPOCH_REPORT_HEADER.join(","));
 
             for row in &self.rows {
+                let values: Vec<String> = row.get_values().iter().map(u128::to_string).collect();
+                println!("{}", values.join(","));
+            }
+        }
+    }
+
+    // one row per epoch, with the mean, standard deviation and percentiles
+    //   of each column of the epoch report across the runs
+    pub fn print_monte_carlo_report(outputs: &[Output]) {
+        let mut header = vec![EPOCH_REPORT_HEADER[0].to_string()];
+        for column in &EPOCH_REPORT_HEADER[1..] {
+            for statistic in &["mean", "sd", "p5", "p50", "p95"] {
+                header.push(format!("{} {}", column, statistic));
+            }
+        }
+        println!("{}", header.join(","));
+
+        for (epoch, row) in outputs[0].rows.iter().enumerate() {
+            let runs: Vec<Vec<u128>> = outputs
+                .iter()
+                .map(|output| output.rows[epoch].get_values())
+                .collect();
+
+            let mut values = vec![row.epoch_id.to_string()];
+            for column in 1..EPOCH_REPORT_HEADER.len() {
+                let samples: Vec<f64> = runs.iter().map(|run| run[column] as f64).collect();
+                let summary = Summary::from_samples(samples);
+
+                values.push(summary.mean.to_string());
+                values.push(summary.sd.to_string());
+                values.push(summary.p5.to_string());
+                values.push(summary.p50.to_string());
+                values.push(summary.p95.to_string());
+            }
+            println!("{}", values.join(","));
+        }
+    }
+
+    pub fn print_monthly_report(&self, config: &Config) {
+        for record in self.get_monthly_report(config) {
+            if config.execution_rewards == ExecutionRewards::None {
                 println!(
-                    "{},{},{},{},{},{},{},{},{},{},{},{}",
-                    row.epoch_id,
-                    row.deltas_head_ffg_rewards,
-                    row.deltas_head_ffg_penalties,
-                    row.deltas_proposer_rewards,
-                    row.deltas_attester_rewards,
-                    row.total_staked_balance,
-                    row.total_effective_balance,
-                    row.max_balance,
-                    row.min_balance,
-                    row.total_validators,
-                    row.total_active_validators,
-                    row.time_elapsed,
+                    "Month number: {}, Total Network Rewards {}",
+                    record.month_number, record.network_percentage_net_rewards
                 );
+                continue;
             }
+
+            println!(
+                "Month number: {}, Total Network Rewards {}, Execution Rewards {}, Total Operator Revenue {}",
+                record.month_number,
+                record.network_percentage_net_rewards,
+                record.network_percentage_execution_rewards,
+                record.network_percentage_net_rewards + record.network_percentage_execution_rewards
+            );
         }
     }
 
-    pub fn print_monthly_report(&self, config: &Config) {
+    pub fn get_monthly_report(&self, config: &Config) -> Vec<MonthlyReportRow> {
         let epochs_per_year = config.epochs;
         let epochs_per_month = epochs_per_year / MONTHS_PER_YEAR;
 
         let mut monthly_report: Vec<MonthlyReportRow> = Vec::new();
         let mut items_to_get = vec![];
 
+        // the stake queued at the start is in the balances from the first epoch
+        let initial_balance = config.total_at_stake_initial + config.total_at_stake_queued;
+
         for epoch in (epochs_per_month..epochs_per_year).step_by(epochs_per_month as usize) {
             items_to_get.push(epoch)
         }
 
         for (index, item) in items_to_get.iter().enumerate() {
             let current_item = &self.rows[*item as usize];
-            let network_percentage_rewards = Output::get_variation_percentage(
-                current_item.total_staked_balance,
-                config.total_at_stake_initial,
-            );
-            let network_percentage_penalties = Output::get_variation_percentage(
-                current_item.deltas_head_ffg_penalties,
-                config.total_at_stake_initial,
+
+            // the balance deposited since the start is principal, not rewards.
+            //   The rest of the variation is net of every penalty.
+            let deposited_balance: u64 = self.rows[..=*item as usize]
+                .iter()
+                .map(|row| row.deposited_balance)
+                .sum();
+            let network_percentage_net_rewards = Output::get_variation_percentage(
+                current_item.total_staked_balance - deposited_balance,
+                initial_balance,
             );
-            let network_percentage_net_rewards =
-                network_percentage_rewards - network_percentage_penalties;
+
+            // the penalties since the start, as a share of the initial stake,
+            //   and the gross rewards they were taken from
+            let penalties: u64 = self.rows[..=*item as usize]
+                .iter()
+                .map(|row| row.get_penalties())
+                .sum();
+            let network_percentage_penalties = penalties as f64 / initial_balance as f64 * 100.0;
+            let network_percentage_rewards =
+                network_percentage_net_rewards + network_percentage_penalties;
+
+            // the execution rewards are not in the balances, so they are
+            //   summed since the start
+            let execution_rewards: u64 = self.rows[..=*item as usize]
+                .iter()
+                .map(|row| row.execution_rewards)
+                .sum();
+            let network_percentage_execution_rewards =
+                execution_rewards as f64 / initial_balance as f64 * 100.0;
 
             monthly_report.push(MonthlyReportRow {
                 month_number: index as u32 + 1u32,
-                network_percentage_rewards: network_percentage_rewards,
-                network_percentage_penalties: network_percentage_penalties,
-                network_percentage_net_rewards: network_percentage_net_rewards,
+                network_percentage_rewards,
+                network_percentage_penalties,
+                network_percentage_net_rewards,
+                network_percentage_execution_rewards,
             });
         }
 
-        for record in monthly_report {
-            println!(
-                "Month number: {}, Total Network Rewards {}",
-                record.month_number, record.network_percentage_net_rewards
-            );
-        }
+        monthly_report
     }
 
     fn get_variation_percentage(new_value: u64, old_value: u64) -> f64 {
@@ -107,21 +190,36 @@ impl Output {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Copy, Clone)]
 pub struct MonthlyReportRow {
     pub month_number: u32,
     pub network_percentage_rewards: f64,
     pub network_percentage_penalties: f64,
     pub network_percentage_net_rewards: f64,
+    pub network_percentage_execution_rewards: f64,
 }
 
 pub struct EpochReportRow {
     pub epoch_id: i32,
 
-    pub deltas_head_ffg_rewards: u64,
-    pub deltas_head_ffg_penalties: u64,
+    pub deltas_source_rewards: u64,
+    pub deltas_source_penalties: u64,
+    pub deltas_target_rewards: u64,
+    pub deltas_target_penalties: u64,
+    pub deltas_head_rewards: u64,
+    pub deltas_head_penalties: u64,
     pub deltas_proposer_rewards: u64,
     pub deltas_attester_rewards: u64,
+    pub deltas_inactivity_penalties: u64,
+    pub deltas_slashing_penalties: u64,
+    pub deltas_whistleblower_rewards: u64,
+    pub deltas_sync_rewards: u64,
+    pub deltas_sync_penalties: u64,
+
+    // priority fees and MEV of the blocks, to the fee recipients of the
+    //   proposers, not to their balances
+    pub execution_rewards: u64,
 
     pub total_staked_balance: u64,
     pub total_effective_balance: u64,
@@ -129,6 +227,21 @@ pub struct EpochReportRow {
     pub min_balance: u64,
     pub total_validators: u64,
     pub total_active_validators: u64,
+    pub epochs_since_finality: u64,
+    pub slashed_validators: u64,
+    pub deposits: u64,
+    pub deposited_balance: u64,
+    pub activated_validators: u64,
+    pub pending_validators: u64,
+    pub voluntary_exits: u64,
+    pub exited_validators: u64,
+
+    // distribution of the inactivity scores of the active validators
+    pub inactivity_score_p50: u64,
+    pub inactivity_score_p95: u64,
+    pub inactivity_score_p99: u64,
+    pub inactivity_score_max: u64,
+    pub validators_with_inactivity_score: u64,
 
     pub time_started: Instant,
     pub time_elapsed: u128,
@@ -139,10 +252,21 @@ impl EpochReportRow {
         EpochReportRow {
             epoch_id: id,
 
-            deltas_head_ffg_rewards: 0,
-            deltas_head_ffg_penalties: 0,
+            deltas_source_rewards: 0,
+            deltas_source_penalties: 0,
+            deltas_target_rewards: 0,
+            deltas_target_penalties: 0,
+            deltas_head_rewards: 0,
+            deltas_head_penalties: 0,
             deltas_proposer_rewards: 0,
             deltas_attester_rewards: 0,
+            deltas_inactivity_penalties: 0,
+            deltas_slashing_penalties: 0,
+            deltas_whistleblower_rewards: 0,
+            deltas_sync_rewards: 0,
+            deltas_sync_penalties: 0,
+
+            execution_rewards: 0,
 
             total_staked_balance: 0,
             total_effective_balance: 0,
@@ -150,6 +274,20 @@ impl EpochReportRow {
             min_balance: 0,
             total_validators: 0,
             total_active_validators: 0,
+            epochs_since_finality: 0,
+            slashed_validators: 0,
+            deposits: 0,
+            deposited_balance: 0,
+            activated_validators: 0,
+            pending_validators: 0,
+            voluntary_exits: 0,
+            exited_validators: 0,
+
+            inactivity_score_p50: 0,
+            inactivity_score_p95: 0,
+            inactivity_score_p99: 0,
+            inactivity_score_max: 0,
+            validators_with_inactivity_score: 0,
 
             time_started: Instant::now(),
             time_elapsed: 0,
@@ -157,33 +295,125 @@ impl EpochReportRow {
     }
 
     pub fn aggregate(&mut self, deltas: &Deltas) {
-        self.deltas_head_ffg_rewards += deltas.head_ffg_reward;
-        self.deltas_head_ffg_penalties += deltas.head_ffg_penalty;
+        self.deltas_source_rewards += deltas.source_reward;
+        self.deltas_source_penalties += deltas.source_penalty;
+        self.deltas_target_rewards += deltas.target_reward;
+        self.deltas_target_penalties += deltas.target_penalty;
+        self.deltas_head_rewards += deltas.head_reward;
+        self.deltas_head_penalties += deltas.head_penalty;
         self.deltas_proposer_rewards += deltas.proposer_reward;
         self.deltas_attester_rewards += deltas.attester_reward;
+        self.deltas_inactivity_penalties += deltas.inactivity_penalty;
+        self.deltas_slashing_penalties += deltas.slashing_penalty;
+        self.deltas_whistleblower_rewards += deltas.whistleblower_reward;
+        self.deltas_sync_rewards += deltas.sync_reward;
+        self.deltas_sync_penalties += deltas.sync_penalty;
     }
 
-    pub fn close(&mut self, state: &State, state_totals: &mut StateTotals) {
-        state_totals.staked_balance = state.get_total_staked_balance();
-        state_totals.active_balance = state.get_total_active_balance();
-        state_totals.sqrt_active_balance = state_totals.active_balance.integer_sqrt();
-        state_totals.active_validators = state.get_total_active_validators();
-        state_totals.matching_balance = state.get_matching_balance();
-        state_totals.adjusted_matching_balance =
-            (state_totals.matching_balance as f32 * state.config.probability_online).floor() as u64;
-        state_totals.max_balance = state.get_max_balance();
-        state_totals.min_balance = state.get_min_balance();
+    // in the order of EPOCH_REPORT_HEADER
+    pub fn get_values(&self) -> Vec<u128> {
+        vec![
+            self.epoch_id as u128,
+            self.deltas_source_rewards as u128,
+            self.deltas_source_penalties as u128,
+            self.deltas_target_rewards as u128,
+            self.deltas_target_penalties as u128,
+            self.deltas_head_rewards as u128,
+            self.deltas_head_penalties as u128,
+            self.deltas_proposer_rewards as u128,
+            self.deltas_attester_rewards as u128,
+            self.deltas_inactivity_penalties as u128,
+            self.deltas_slashing_penalties as u128,
+            self.deltas_whistleblower_rewards as u128,
+            self.deltas_sync_rewards as u128,
+            self.deltas_sync_penalties as u128,
+            self.execution_rewards as u128,
+            self.total_staked_balance as u128,
+            self.total_effective_balance as u128,
+            self.max_balance as u128,
+            self.min_balance as u128,
+            self.total_validators as u128,
+            self.total_active_validators as u128,
+            self.epochs_since_finality as u128,
+            self.slashed_validators as u128,
+            self.deposits as u128,
+            self.deposited_balance as u128,
+            self.activated_validators as u128,
+            self.pending_validators as u128,
+            self.voluntary_exits as u128,
+            self.exited_validators as u128,
+            self.inactivity_score_p50 as u128,
+            self.inactivity_score_p95 as u128,
+            self.inactivity_score_p99 as u128,
+            self.inactivity_score_max as u128,
+            self.validators_with_inactivity_score as u128,
+            self.time_elapsed,
+        ]
+    }
+
+    // rewards and penalties of the source, target and head votes
+    pub fn get_vote_rewards(&self) -> u64 {
+        self.deltas_source_rewards + self.deltas_target_rewards + self.deltas_head_rewards
+    }
+
+    pub fn get_vote_penalties(&self) -> u64 {
+        self.deltas_source_penalties + self.deltas_target_penalties + self.deltas_head_penalties
+    }
+
+    // every penalty taken out of the balances
+    pub fn get_penalties(&self) -> u64 {
+        self.get_vote_penalties()
+            + self.deltas_inactivity_penalties
+            + self.deltas_slashing_penalties
+            + self.deltas_sync_penalties
+    }
+
+    pub fn close(&mut self, state: &State, state_totals: &StateTotals) {
+        // the totals are maintained incrementally during the epoch processing
+        debug_assert_eq!(&StateTotals::new(state), state_totals);
 
         self.total_staked_balance = state_totals.staked_balance;
         self.total_effective_balance = state_totals.active_balance;
         self.max_balance = state_totals.max_balance;
         self.min_balance = state_totals.min_balance;
-        self.total_validators = state.validators.len() as u64;
+        self.total_validators = state.get_total_validators();
         self.total_active_validators = state_totals.active_validators;
+        // the delay the inactivity leak is based on
+        self.epochs_since_finality = state.get_finality_delay();
+
+        // the scores stay at 0 with Phase 0, which saves a scan of the registry
+        if state.config.fork == Fork::Altair {
+            let inactivity_scores = state.get_inactivity_scores();
+            self.inactivity_score_p50 = get_histogram_percentile(&inactivity_scores, 0.50);
+            self.inactivity_score_p95 = get_histogram_percentile(&inactivity_scores, 0.95);
+            self.inactivity_score_p99 = get_histogram_percentile(&inactivity_scores, 0.99);
+            self.inactivity_score_max = inactivity_scores.keys().next_back().copied().unwrap_or(0);
+            self.validators_with_inactivity_score = inactivity_scores
+                .range(1..)
+                .map(|(_, validators)| validators)
+                .sum();
+        }
+
         self.time_elapsed = self.time_started.elapsed().as_micros();
     }
 }
 
+// nearest rank, on the number of samples of each value
+fn get_histogram_percentile(histogram: &BTreeMap<u64, u64>, p: f64) -> u64 {
+    let samples: u64 = histogram.values().sum();
+    let rank = ((p * samples as f64).ceil() as u64).max(1);
+
+    let mut cumulative_samples = 0;
+    for (value, count) in histogram {
+        cumulative_samples += count;
+        if cumulative_samples >= rank {
+            return *value;
+        }
+    }
+
+    0
+}
+
 // TODO: Tests
 // - Output::new()
 // - Output::push()
@@ -192,3 +422,192 @@ impl EpochReportRow {
 // - EpochReportRow::open()
 // - EpochReportRow::aggregate()
 // - EpochReportRow::close()
+
+// statistics of a column of the epoch report across Monte Carlo runs
+pub struct Summary {
+    pub mean: f64,
+    pub sd: f64,
+    pub p5: f64,
+    pub p50: f64,
+    pub p95: f64,
+}
+
+impl Summary {
+    pub fn from_samples(mut samples: Vec<f64>) -> Summary {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+
+        // sample standard deviation
+        let sd = if samples.len() > 1 {
+            (samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        Summary {
+            mean,
+            sd,
+            p5: Summary::get_percentile(&samples, 0.05),
+            p50: Summary::get_percentile(&samples, 0.50),
+            p95: Summary::get_percentile(&samples, 0.95),
+        }
+    }
+
+    // nearest rank, on sorted samples
+    fn get_percentile(sorted_samples: &[f64], p: f64) -> f64 {
+        let rank = (p * sorted_samples.len() as f64).ceil() as usize;
+        sorted_samples[rank.max(1) - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_from_samples() {
+        let samples: Vec<f64> = (1..=100).map(|x| x as f64).collect();
+        let summary = Summary::from_samples(samples);
+
+        assert_eq!(50.5, summary.mean);
+        assert_eq!(29.01, (summary.sd * 100.0).round() / 100.0);
+        assert_eq!(5.0, summary.p5);
+        assert_eq!(50.0, summary.p50);
+        assert_eq!(95.0, summary.p95);
+
+        let summary = Summary::from_samples(vec![7.0]);
+        assert_eq!(7.0, summary.mean);
+        assert_eq!(0.0, summary.sd);
+        assert_eq!(7.0, summary.p5);
+        assert_eq!(7.0, summary.p95);
+    }
+
+    // rows of a network earning 1 ETH of rewards per epoch after the first
+    //   one, and losing the given penalties at every epoch, plus the stake
+    //   queued at the start and the deposits of each epoch
+    fn get_output(queued_balance: u64, deposits: &[u64], penalties: u64) -> Output {
+        let mut output = Output::new();
+        let mut total_deposited = 0;
+
+        for (epoch, deposited_balance) in deposits.iter().enumerate() {
+            total_deposited += deposited_balance;
+
+            let mut row = EpochReportRow::open(epoch as i32);
+            row.deposited_balance = *deposited_balance;
+            row.deltas_attester_rewards = if epoch > 0 { 1_000_000_000 } else { 0 };
+            row.deltas_target_penalties = penalties;
+            row.total_staked_balance = 500_000_000_000_000
+                + queued_balance
+                + epoch as u64 * 1_000_000_000
+                + total_deposited
+                - (epoch as u64 + 1) * penalties;
+            output.push(row);
+        }
+
+        output
+    }
+
+    #[test]
+    fn monthly_report_with_deposits() {
+        let config = Config {
+            epochs: 24,
+            ..Config::default()
+        };
+        let mut deposits = vec![0; 24];
+        deposits[1] = 32_000_000_000;
+        deposits[5] = 64_000_000_000;
+
+        let without_deposits = get_output(0, &[0; 24], 0).get_monthly_report(&config);
+        let with_deposits = get_output(0, &deposits, 0).get_monthly_report(&config);
+
+        // the deposits do not count as rewards
+        assert_eq!(11, with_deposits.len());
+        for (expected, record) in without_deposits.iter().zip(&with_deposits) {
+            assert_eq!(
+                expected.network_percentage_rewards,
+                record.network_percentage_rewards
+            );
+        }
+        // 4 ETH at the end of the second month
+        assert_eq!(
+            0.0008,
+            (with_deposits[1].network_percentage_rewards * 10_000.0).round() / 10_000.0
+        );
+    }
+
+    #[test]
+    fn monthly_report_with_queued_stake() {
+        let config = Config {
+            epochs: 24,
+            total_at_stake_queued: 500_000_000_000_000,
+            ..Config::default()
+        };
+        let with_queued_stake = get_output(500_000_000_000_000, &[0; 24], 0);
+
+        // the queued stake is principal, the rewards are shared with it
+        assert_eq!(
+            0.0004,
+            (with_queued_stake.get_monthly_report(&config)[1].network_percentage_rewards
+                * 10_000.0)
+                .round()
+                / 10_000.0
+        );
+    }
+
+    #[test]
+    fn monthly_report_net_rewards_and_revenue() {
+        let config = Config {
+            epochs: 24,
+            ..Config::default()
+        };
+        // 0.5 ETH of penalties and 2 ETH of execution rewards per epoch
+        let mut output = get_output(0, &[0; 24], 500_000_000);
+        for row in &mut output.rows {
+            row.execution_rewards = 2_000_000_000;
+        }
+
+        let round = |percentage: f64| (percentage * 1_000_000.0).round() / 1_000_000.0;
+        let record = output.get_monthly_report(&config)[1];
+
+        // at epoch 4: 4 ETH of rewards, 2.5 ETH of penalties and 10 ETH of
+        //   execution rewards, on 500_000 ETH
+        assert_eq!(0.0008, round(record.network_percentage_rewards));
+        assert_eq!(0.0005, round(record.network_percentage_penalties));
+        assert_eq!(0.0003, round(record.network_percentage_net_rewards));
+        assert_eq!(0.002, round(record.network_percentage_execution_rewards));
+        assert_eq!(
+            0.0023,
+            round(
+                record.network_percentage_net_rewards + record.network_percentage_execution_rewards
+            )
+        );
+    }
+
+    #[test]
+    fn epochs_since_finality() {
+        let mut state = State::from_config(Config::default());
+        let state_totals = StateTotals::new(&state);
+        state.current_epoch = 10;
+        state.finalized_epoch = 5;
+
+        let mut row = EpochReportRow::open(10);
+        row.close(&state, &state_totals);
+
+        assert_eq!(4, row.epochs_since_finality);
+        assert_eq!(state.get_finality_delay(), row.epochs_since_finality);
+    }
+
+    #[test]
+    fn histogram_percentile() {
+        let histogram: BTreeMap<u64, u64> =
+            vec![(0, 90), (4, 5), (8, 4), (40, 1)].into_iter().collect();
+
+        assert_eq!(0, get_histogram_percentile(&histogram, 0.50));
+        assert_eq!(4, get_histogram_percentile(&histogram, 0.95));
+        assert_eq!(8, get_histogram_percentile(&histogram, 0.99));
+        assert_eq!(40, get_histogram_percentile(&histogram, 1.0));
+        assert_eq!(0, get_histogram_percentile(&BTreeMap::new(), 0.50));
+    }
+}
@@ -0,0 +1,391 @@
+////////////////////////////////////////////////////////////////////////////////
+//
+// A scenario file, describing a whole simulation in TOML or JSON
+//
+////////////////////////////////////////////////////////////////////////////////
+
+use super::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// every field is optional, the config keeping its value otherwise. The
+//   options given as strings on the command line use the same syntax here.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub epochs: Option<i32>,
+    pub runs: Option<u64>,
+    pub seed: Option<u64>,
+    pub fork: Option<String>,
+    pub engine: Option<String>,
+    pub cohort_bucket: Option<u64>,
+
+    #[serde(default)]
+    pub output: ScenarioOutput,
+
+    #[serde(default)]
+    pub validators: ScenarioValidators,
+
+    #[serde(default)]
+    pub probabilities: ScenarioProbabilities,
+
+    #[serde(default)]
+    pub spec: ScenarioSpec,
+
+    pub exit_policy: Option<String>,
+
+    // a model of the execution rewards of each block, or a file of
+    //   historical ones, which takes precedence
+    pub execution_rewards: Option<String>,
+    pub execution_rewards_file: Option<String>,
+
+    // the schedules of events
+    #[serde(default)]
+    pub deposits: Vec<ScenarioDeposit>,
+    #[serde(default)]
+    pub slashings: Vec<ScenarioSlashing>,
+    #[serde(default)]
+    pub outages: Vec<ScenarioOutage>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioOutput {
+    // epoch or monthly
+    pub report: Option<String>,
+}
+
+// amounts in ETH
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioValidators {
+    pub initial_stake: Option<u64>,
+    pub queued_stake: Option<u64>,
+    pub deposits_per_epoch: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioProbabilities {
+    pub online: Option<f32>,
+    pub honest: Option<f32>,
+    pub dishonest_behaviour: Option<String>,
+    pub target: Option<f32>,
+    pub head: Option<f32>,
+    pub slashed: Option<f32>,
+    pub inclusion_delay: Option<String>,
+}
+
+// a preset, and the constants overriding it, named as in the spec
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioSpec {
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub overrides: BTreeMap<String, u64>,
+}
+
+// as the lines of a deposits file, with the amount in ETH
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioDeposit {
+    pub epoch: i32,
+    pub amount: f64,
+    pub validator_index: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioSlashing {
+    pub epoch: i32,
+    pub count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioOutage {
+    pub from_epoch: i32,
+    pub to_epoch: i32,
+    pub probability_online: f32,
+}
+
+impl Scenario {
+    // the format is given by the extension of the file
+    pub fn from_file(path: &str) -> Result<Scenario, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read scenario {}: {}", path, e))?;
+
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("toml") => Scenario::parse_toml(&contents),
+            Some("json") => Scenario::parse_json(&contents),
+            _ => Err("scenario files should be .toml or .json".to_string()),
+        }
+    }
+
+    pub fn parse_toml(contents: &str) -> Result<Scenario, String> {
+        toml::from_str(contents).map_err(|e| format!("invalid scenario: {}", e))
+    }
+
+    pub fn parse_json(contents: &str) -> Result<Scenario, String> {
+        serde_json::from_str(contents).map_err(|e| format!("invalid scenario: {}", e))
+    }
+
+    // sets the fields given by the scenario on the config
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        // first, as the stake and deposits are split by MAX_EFFECTIVE_BALANCE
+        if let Some(preset) = &self.spec.preset {
+            config.spec = SpecConstants::from_preset(preset)?;
+        }
+        for (name, value) in &self.spec.overrides {
+            config.spec.set(name, *value)?;
+        }
+
+        if let Some(epochs) = self.epochs {
+            config.epochs = epochs;
+        }
+        if let Some(runs) = self.runs {
+            config.runs = runs;
+        }
+        if let Some(seed) = self.seed {
+            config.seed = seed;
+        }
+        if let Some(fork) = &self.fork {
+            config.fork = Config::parse_fork(fork)?;
+        }
+        if let Some(engine) = &self.engine {
+            config.engine = Config::parse_engine(engine)?;
+        }
+        if let Some(cohort_bucket) = self.cohort_bucket {
+            config.cohort_bucket = cohort_bucket;
+        }
+
+        if let Some(report) = &self.output.report {
+            config.printing_output = report.clone();
+        }
+
+        if let Some(initial_stake) = self.validators.initial_stake {
+            config.total_at_stake_initial = initial_stake * 1_000_000_000;
+        }
+        if let Some(queued_stake) = self.validators.queued_stake {
+            config.total_at_stake_queued = queued_stake * 1_000_000_000;
+        }
+        if let Some(deposits_per_epoch) = self.validators.deposits_per_epoch {
+            config.deposits_per_epoch = deposits_per_epoch;
+        }
+
+        let probabilities = &self.probabilities;
+        if let Some(online) = probabilities.online {
+            config.probability_online = online;
+        }
+        if let Some(honest) = probabilities.honest {
+            config.probability_honest = honest;
+        }
+        if let Some(dishonest_behaviour) = &probabilities.dishonest_behaviour {
+            config.dishonest_behaviour = Config::parse_dishonest_behaviour(dishonest_behaviour)?;
+        }
+        if let Some(target) = probabilities.target {
+            config.probability_target = target;
+        }
+        if let Some(head) = probabilities.head {
+            config.probability_head = head;
+        }
+        if let Some(slashed) = probabilities.slashed {
+            config.probability_slashed = slashed;
+        }
+        if let Some(inclusion_delay) = &probabilities.inclusion_delay {
+            config.inclusion_delay = Config::parse_inclusion_delay(inclusion_delay)?;
+        }
+
+        if let Some(exit_policy) = &self.exit_policy {
+            config.exit_policy = Config::parse_exit_policy(exit_policy)?;
+        }
+
+        if let Some(execution_rewards) = &self.execution_rewards {
+            config.execution_rewards = Config::parse_execution_rewards(execution_rewards)?;
+        }
+        if let Some(path) = &self.execution_rewards_file {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("could not read execution_rewards_file {}: {}", path, e))?;
+            config.execution_rewards = ExecutionRewards::parse_csv(&contents)?;
+        }
+
+        if !self.deposits.is_empty() {
+            config.deposit_schedule =
+                Deposit::parse_csv(&self.get_deposits_csv(), config.spec.max_effective_balance)?;
+        }
+        if !self.slashings.is_empty() {
+            config.slashing_schedule = self.slashings.iter().map(|s| (s.epoch, s.count)).collect();
+        }
+        if !self.outages.is_empty() {
+            config.outages = self
+                .outages
+                .iter()
+                .map(|o| Outage {
+                    from_epoch: o.from_epoch,
+                    to_epoch: o.to_epoch,
+                    probability_online: o.probability_online,
+                })
+                .collect();
+        }
+
+        Ok(())
+    }
+
+    // deposits go through the parsing of a deposits file, so the ETH of a
+    //   new validator is split the same way
+    fn get_deposits_csv(&self) -> String {
+        self.deposits
+            .iter()
+            .map(|d| match d.validator_index {
+                Some(validator_index) => format!("{},{},{}\n", d.epoch, d.amount, validator_index),
+                None => format!("{},{}\n", d.epoch, d.amount),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO_TOML: &str = r#"
+        epochs = 100
+        seed = 42
+        fork = "altair"
+        exit_policy = "rate:4"
+        execution_rewards = "lognormal:40000000:1.2"
+
+        [output]
+        report = "monthly"
+
+        [validators]
+        initial_stake = 1000000
+
+        [probabilities]
+        online = 0.95
+        dishonest_behaviour = "wrong_head"
+
+        [[deposits]]
+        epoch = 10
+        amount = 64
+
+        [[deposits]]
+        epoch = 20
+        amount = 1.5
+        validator_index = 3
+
+        [[slashings]]
+        epoch = 50
+        count = 2
+
+        [[outages]]
+        from_epoch = 30
+        to_epoch = 40
+        probability_online = 0.5
+    "#;
+
+    #[test]
+    fn parse_toml() {
+        let mut config = Config::default();
+        Scenario::parse_toml(SCENARIO_TOML)
+            .unwrap()
+            .apply(&mut config)
+            .unwrap();
+
+        assert_eq!(100, config.epochs);
+        assert_eq!(42, config.seed);
+        assert_eq!(Fork::Altair, config.fork);
+        assert_eq!("monthly", config.printing_output);
+        assert_eq!(1_000_000_000_000_000, config.total_at_stake_initial);
+        assert_eq!(0.95, config.probability_online);
+        assert_eq!(DishonestBehaviour::WrongHead, config.dishonest_behaviour);
+        assert_eq!(ExitPolicy::FixedRate(4), config.exit_policy);
+        assert_eq!(
+            ExecutionRewards::LogNormal(40_000_000.0, 1.2),
+            config.execution_rewards
+        );
+        assert_eq!(3, config.deposit_schedule.len());
+        assert_eq!(
+            Deposit {
+                epoch: 20,
+                amount: 1_500_000_000,
+                validator_index: Some(3),
+            },
+            config.deposit_schedule[2]
+        );
+        assert_eq!(vec![(50, 2)], config.slashing_schedule);
+        assert_eq!(
+            Some(0.5),
+            config.get_outage(35).map(|o| o.probability_online)
+        );
+
+        // the fields not in the scenario keep their value
+        assert_eq!(1.0, config.probability_honest);
+        assert_eq!(Engine::Validators, config.engine);
+    }
+
+    #[test]
+    fn parse_json() {
+        let mut config = Config::default();
+        Scenario::parse_json(
+            r#"{
+                "epochs": 100,
+                "probabilities": { "online": 0.95 },
+                "slashings": [{ "epoch": 50, "count": 2 }]
+            }"#,
+        )
+        .unwrap()
+        .apply(&mut config)
+        .unwrap();
+
+        assert_eq!(100, config.epochs);
+        assert_eq!(0.95, config.probability_online);
+        assert_eq!(vec![(50, 2)], config.slashing_schedule);
+    }
+
+    #[test]
+    fn parse_toml_spec() {
+        let mut config = Config::default();
+        Scenario::parse_toml(
+            r#"
+            [validators]
+            initial_stake = 1000000
+
+            [spec]
+            preset = "minimal"
+
+            [spec.overrides]
+            BASE_REWARD_FACTOR = 128
+            MAX_EFFECTIVE_BALANCE = 64000000000
+        "#,
+        )
+        .unwrap()
+        .apply(&mut config)
+        .unwrap();
+
+        assert_eq!(8, config.spec.slots_per_epoch);
+        assert_eq!(128, config.spec.base_reward_factor);
+
+        // the validators are created with the overridden balance
+        let state = State::from_config(config.build().unwrap());
+        assert_eq!(15_625, state.get_total_validators());
+    }
+
+    #[test]
+    fn parse_toml_unknown_field() {
+        assert!(Scenario::parse_toml("epoch = 100").is_err());
+    }
+
+    #[test]
+    fn example_scenario() {
+        let mut config = Config::default();
+        Scenario::from_file("scenarios/example.toml")
+            .unwrap()
+            .apply(&mut config)
+            .unwrap();
+
+        assert!(config.build().is_ok());
+    }
+}
@@ -9,40 +9,160 @@
 use super::*;
 use integer_sqrt::IntegerSquareRoot;
 use rand::prelude::*;
+use std::cmp;
+use std::collections::{BTreeMap, VecDeque};
 
 pub struct State {
     pub config: config::Config,
     pub validators: Vec<Validator>,
+
+    // the validators tracked by count, with the cohorts engine
+    pub cohorts: Vec<Cohort>,
+
+    // the validators of the cohorts past their exit epoch, which keep their balances
+    pub exited_cohorts: Vec<Cohort>,
+
+    // justification and finalization, tracked by epoch only
+    pub current_epoch: u64,
+    pub justification_bits: [bool; config::JUSTIFICATION_BITS_LENGTH],
+    pub previous_justified_epoch: u64,
+    pub current_justified_epoch: u64,
+    pub finalized_epoch: u64,
+
+    // effective balances slashed during the last EPOCHS_PER_SLASHINGS_VECTOR epochs
+    pub slashings: Vec<u64>,
+
+    // the latest exit epoch assigned, and how many validators exit at it.
+    //   it saves scanning the registry at each `initiate_validator_exit`.
+    pub exit_queue_epoch: u64,
+    pub exit_queue_churn: u64,
+
+    // `get_validator_churn_limit()` of the current epoch. The active
+    //   validators only change at epoch boundaries, so it is set once per
+    //   epoch instead of scanning the registry at each activation or exit.
+    pub validator_churn_limit: u64,
+
+    // deposits waiting to be included in a block
+    pub pending_deposits: VecDeque<Deposit>,
+
+    // indices of the validators of the sync committee of the period, with Altair
+    pub sync_committee: Vec<usize>,
+
+    // the next historical block value to replay, for the execution rewards
+    pub execution_rewards_index: usize,
+
+    // every random draw of the simulation comes from here
+    pub rng: StdRng,
 }
 
 impl State {
-    pub fn new() -> State {
-        let config = config::Config::new();
-
-        let number_of_validators = config.total_at_stake_initial / config::MAX_EFFECTIVE_BALANCE;
+    pub fn from_config(config: config::Config) -> State {
+        let spec = &config.spec;
+        let number_of_validators = config.total_at_stake_initial / spec.max_effective_balance;
+        let number_of_queued_validators = config.total_at_stake_queued / spec.max_effective_balance;
         let mut validators = vec![];
-
-        for _ in 0..number_of_validators {
-            validators.push(Validator {
-                balance: config::MAX_EFFECTIVE_BALANCE,
-                effective_balance: config::MAX_EFFECTIVE_BALANCE,
-                is_active: true,
-                is_slashed: false,
-                has_matched_source: false,
-                has_matched_head: false,
-                has_matched_target: false,
-                is_proposer: false,
+        let mut cohorts = vec![];
+
+        // genesis validators are active from epoch 0. With the cohorts and
+        //   analytic engines, they all start in the same cohort.
+        if config.engine != Engine::Validators {
+            cohorts.push(Cohort {
+                count: number_of_validators,
+                balance_sum: number_of_validators * spec.max_effective_balance,
+                effective_balance: spec.max_effective_balance,
+                inactivity_score: 0,
+                inactivity_score_fraction: 0.0,
+                exit_epoch: config::FAR_FUTURE_EPOCH,
             });
+        } else {
+            for _ in 0..number_of_validators {
+                let mut validator = Validator::new(spec.max_effective_balance, spec);
+                validator.activation_eligibility_epoch = 0;
+                validator.activation_epoch = 0;
+                validator.is_active = true;
+
+                validators.push(validator);
+            }
         }
 
-        State {
-            config: config,
-            validators: validators,
+        // the rest have to go through the activation queue
+        for _ in 0..number_of_queued_validators {
+            validators.push(Validator::new(spec.max_effective_balance, spec));
         }
+
+        let slashings = vec![0; spec.epochs_per_slashings_vector as usize];
+        let rng = StdRng::seed_from_u64(config.seed);
+
+        let mut state = State {
+            config,
+            validators,
+            cohorts,
+            exited_cohorts: vec![],
+            current_epoch: 0,
+            justification_bits: [false; config::JUSTIFICATION_BITS_LENGTH],
+            previous_justified_epoch: 0,
+            current_justified_epoch: 0,
+            finalized_epoch: 0,
+            slashings,
+            exit_queue_epoch: 0,
+            exit_queue_churn: 0,
+            validator_churn_limit: 0,
+            pending_deposits: VecDeque::new(),
+            sync_committee: vec![],
+            execution_rewards_index: 0,
+            rng,
+        };
+        state.update_validator_churn_limit(number_of_validators);
+
+        state
+    }
+
+    pub fn get_previous_epoch(&self) -> u64 {
+        self.current_epoch.saturating_sub(1)
+    }
+
+    pub fn get_finality_delay(&self) -> u64 {
+        self.get_previous_epoch()
+            .saturating_sub(self.finalized_epoch)
+    }
+
+    pub fn is_in_inactivity_leak(&self) -> bool {
+        self.get_finality_delay() > config::MIN_EPOCHS_TO_INACTIVITY_PENALTY
+    }
+
+    pub fn get_validator_churn_limit(&self) -> u64 {
+        self.validator_churn_limit
+    }
+
+    // to be called at the start of each epoch, with its active validators
+    pub fn update_validator_churn_limit(&mut self, active_validators: u64) {
+        self.validator_churn_limit = cmp::max(
+            config::MIN_PER_EPOCH_CHURN_LIMIT,
+            active_validators / self.config.spec.churn_limit_quotient,
+        );
+    }
+
+    pub fn get_total_validators(&self) -> u64 {
+        self.validators.len() as u64
+            + self
+                .cohorts
+                .iter()
+                .chain(self.exited_cohorts.iter())
+                .map(|c| c.count)
+                .sum::<u64>()
     }
 
     pub fn get_total_staked_balance(&self) -> u64 {
-        self.validators.iter().map(|v: &Validator| v.balance).sum()
+        self.validators
+            .iter()
+            .map(|v: &Validator| v.balance)
+            .sum::<u64>()
+            + self
+                .cohorts
+                .iter()
+                .chain(self.exited_cohorts.iter())
+                .map(|c| c.balance_sum)
+                .sum::<u64>()
     }
 
     pub fn get_total_active_balance(&self) -> u64 {
@@ -57,14 +177,16 @@ impl State {
                     }
                 },
             )
-            .sum()
+            .sum::<u64>()
+            + self.get_cohorts_active_balance()
     }
 
     pub fn get_total_active_validators(&self) -> u64 {
         self.validators
             .iter()
             .map(|v: &Validator| if v.is_active { 1 } else { 0 })
-            .sum()
+            .sum::<u64>()
+            + self.cohorts.iter().map(|c| c.count).sum::<u64>()
     }
 
     pub fn get_matching_balance(&self) -> u64 {
@@ -77,6 +199,15 @@ impl State {
                     0
                 }
             })
+            .sum::<u64>()
+            + self.get_cohorts_active_balance()
+    }
+
+    // validators of the cohorts are all active and unslashed
+    fn get_cohorts_active_balance(&self) -> u64 {
+        self.cohorts
+            .iter()
+            .map(|c| c.count * c.effective_balance)
             .sum()
     }
 
@@ -84,27 +215,55 @@ impl State {
         self.validators
             .iter()
             .map(|v: &Validator| v.balance)
-            .fold(0, std::cmp::max)
+            .chain(
+                self.cohorts
+                    .iter()
+                    .chain(self.exited_cohorts.iter())
+                    .map(|c| c.get_balance()),
+            )
+            .fold(0, cmp::max)
+    }
+
+    // number of active validators with each inactivity score
+    pub fn get_inactivity_scores(&self) -> BTreeMap<u64, u64> {
+        let mut inactivity_scores = BTreeMap::new();
+
+        for validator in self.validators.iter().filter(|v| v.is_active) {
+            *inactivity_scores
+                .entry(validator.inactivity_score)
+                .or_insert(0) += 1;
+        }
+        for cohort in &self.cohorts {
+            *inactivity_scores
+                .entry(cohort.inactivity_score)
+                .or_insert(0) += cohort.count;
+        }
+
+        inactivity_scores
     }
 
     pub fn get_min_balance(&self) -> u64 {
         self.validators
             .iter()
             .map(|v: &Validator| v.balance)
-            .fold(std::u64::MAX, std::cmp::min)
+            .chain(
+                self.cohorts
+                    .iter()
+                    .chain(self.exited_cohorts.iter())
+                    .map(|c| c.get_balance()),
+            )
+            .fold(u64::MAX, cmp::min)
     }
 
-    pub fn pick_epoch_proposers(&self) -> Vec<usize> {
-        let mut rng = thread_rng();
-
+    pub fn pick_epoch_proposers(&mut self) -> Vec<usize> {
         let mut proposer_indices = vec![];
 
         let n = self.validators.len();
-        let proposers_per_epoch = 32;
-        let max_effective_balance = 32_000_000_000;
+        let proposers_per_epoch = self.config.spec.slots_per_epoch as usize;
+        let max_effective_balance = self.config.spec.max_effective_balance;
         let max_random_byte = 255;
 
-        if self.get_total_active_validators() < 32 {
+        if self.get_total_active_validators() < proposers_per_epoch as u64 {
             panic!("not enough active validators");
         }
 
@@ -113,7 +272,7 @@ impl State {
                 break;
             }
 
-            let candidate_index = rng.gen_range(0, n);
+            let candidate_index = self.rng.gen_range(0, n);
 
             if self.validators[candidate_index].is_slashed
                 || !self.validators[candidate_index].is_active
@@ -123,7 +282,7 @@ impl State {
             }
 
             // effective balance bias on proposer election
-            let random_byte = rng.gen_range(0, 255);
+            let random_byte = self.rng.gen_range(0, 255);
             if self.validators[candidate_index].effective_balance * max_random_byte
                 >= random_byte * max_effective_balance
             {
@@ -133,21 +292,63 @@ impl State {
             }
         }
 
-        let mut proposer_bitmap = vec![0; n];
-        for index in &proposer_indices {
-            proposer_bitmap[*index as usize] = 1;
+        proposer_indices
+    }
+
+    // SPEC: get_next_sync_committee_indices
+    //   as the proposers, but with replacement: a validator may hold more
+    //   than one seat of the committee
+    pub fn pick_sync_committee(&mut self) -> Vec<usize> {
+        let mut sync_committee = vec![];
+
+        let n = self.validators.len();
+        let sync_committee_size = self.config.spec.sync_committee_size as usize;
+        let max_effective_balance = self.config.spec.max_effective_balance;
+        let max_random_byte = 255;
+
+        if self.get_total_active_validators() == 0 {
+            panic!("not enough active validators");
+        }
+
+        while sync_committee.len() < sync_committee_size {
+            let candidate_index = self.rng.gen_range(0, n);
+
+            if !self.validators[candidate_index].is_active {
+                continue;
+            }
+
+            // effective balance bias on the election
+            let random_byte = self.rng.gen_range(0, 255);
+            if self.validators[candidate_index].effective_balance * max_random_byte
+                >= random_byte * max_effective_balance
+            {
+                sync_committee.push(candidate_index);
+            }
+        }
+
+        sync_committee
+    }
+
+    // 1 for the proposers of the epoch, 0 for the rest
+    pub fn get_proposer_bitmap(&self, proposer_indices: &[usize]) -> Vec<usize> {
+        let mut proposer_bitmap = vec![0; self.validators.len()];
+        for index in proposer_indices {
+            proposer_bitmap[*index] = 1;
         }
 
         proposer_bitmap
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct StateTotals {
     pub staked_balance: u64,
     pub active_balance: u64,
     pub sqrt_active_balance: u64,
     pub matching_balance: u64,
-    pub adjusted_matching_balance: u64,
+    pub source_attesting_balance: u64,
+    pub target_attesting_balance: u64,
+    pub head_attesting_balance: u64,
     pub max_balance: u64,
     pub min_balance: u64,
     pub active_validators: u64,
@@ -158,20 +359,114 @@ impl StateTotals {
         let total_active_balance = state.get_total_active_balance();
         let matching_balance = state.get_matching_balance();
 
-        StateTotals {
+        let mut state_totals = StateTotals {
             staked_balance: state.get_total_staked_balance(),
             active_balance: total_active_balance,
             sqrt_active_balance: total_active_balance.integer_sqrt(),
             active_validators: state.get_total_active_validators(),
-            matching_balance: matching_balance,
-            adjusted_matching_balance: (matching_balance as f32 * state.config.probability_online)
-                .floor() as u64,
+            matching_balance,
+            source_attesting_balance: 0,
+            target_attesting_balance: 0,
+            head_attesting_balance: 0,
             max_balance: state.get_max_balance(),
             min_balance: state.get_min_balance(),
+        };
+        state_totals.update_attesting_balances(&state.config);
+
+        state_totals
+    }
+
+    // attestations are not simulated one by one: the attesting balances are
+    //   the expected share of the matching balance getting each vote right,
+    //   and with Altair, in time for its participation flag
+    pub fn update_attesting_balances(&mut self, config: &Config) {
+        let matching_balance = self.matching_balance as f64;
+        let slots_per_epoch = config.spec.slots_per_epoch;
+
+        self.source_attesting_balance = (matching_balance
+            * config.get_probability_source() as f64
+            * config.get_probability_timely(slots_per_epoch.integer_sqrt()))
+        .floor() as u64;
+        self.target_attesting_balance = (matching_balance
+            * config.get_probability_target() as f64
+            * config.get_probability_timely(slots_per_epoch))
+        .floor() as u64;
+        self.head_attesting_balance = (matching_balance
+            * config.get_probability_head() as f64
+            * config.get_probability_timely(config::MIN_ATTESTATION_INCLUSION_DELAY))
+        .floor() as u64;
+    }
+
+    // totals to be filled incrementally, validator by validator
+    pub fn empty() -> StateTotals {
+        StateTotals {
+            staked_balance: 0,
+            active_balance: 0,
+            sqrt_active_balance: 0,
+            matching_balance: 0,
+            source_attesting_balance: 0,
+            target_attesting_balance: 0,
+            head_attesting_balance: 0,
+            max_balance: 0,
+            min_balance: u64::MAX,
+            active_validators: 0,
         }
     }
 
-    //pub fill_totals_from_state
+    pub fn add_validator(&mut self, validator: &Validator) {
+        self.staked_balance += validator.balance;
+        self.max_balance = cmp::max(self.max_balance, validator.balance);
+        self.min_balance = cmp::min(self.min_balance, validator.balance);
+
+        if validator.is_active {
+            self.active_balance += validator.effective_balance;
+            self.active_validators += 1;
+
+            if !validator.is_slashed {
+                self.matching_balance += validator.effective_balance;
+            }
+        }
+    }
+
+    // adds up the totals of another set of validators
+    pub fn merge(&mut self, other: &StateTotals) {
+        self.staked_balance += other.staked_balance;
+        self.active_balance += other.active_balance;
+        self.active_validators += other.active_validators;
+        self.matching_balance += other.matching_balance;
+        self.max_balance = cmp::max(self.max_balance, other.max_balance);
+        self.min_balance = cmp::min(self.min_balance, other.min_balance);
+    }
+
+    // to be called before the activity flag of the validator changes
+    pub fn update_activity(&mut self, validator: &Validator, is_active: bool) {
+        if validator.is_active == is_active {
+            return;
+        }
+
+        let effective_balance = validator.effective_balance;
+        let matching_balance = if validator.is_slashed {
+            0
+        } else {
+            effective_balance
+        };
+
+        if is_active {
+            self.active_balance += effective_balance;
+            self.active_validators += 1;
+            self.matching_balance += matching_balance;
+        } else {
+            self.active_balance -= effective_balance;
+            self.active_validators -= 1;
+            self.matching_balance -= matching_balance;
+        }
+    }
+
+    // the totals derived from the ones added up
+    pub fn finalize(&mut self, config: &Config) {
+        self.sqrt_active_balance = self.active_balance.integer_sqrt();
+        self.update_attesting_balances(config);
+    }
 }
 
 // TODO: Test
@@ -188,31 +483,117 @@ mod tests {
         is_slashed: bool,
     ) -> Validator {
         Validator {
-            balance: balance,
-            effective_balance: effective_balance,
-            is_active: is_active,
-            is_slashed: is_slashed,
+            balance,
+            effective_balance,
+            is_active,
+            is_slashed,
             has_matched_source: false,
             has_matched_head: false,
+            has_equivocated: false,
             has_matched_target: false,
             is_proposer: false,
+            activation_eligibility_epoch: 0,
+            activation_epoch: 0,
+            exit_epoch: config::FAR_FUTURE_EPOCH,
+            withdrawable_epoch: config::FAR_FUTURE_EPOCH,
+            inactivity_score: 0,
         }
     }
 
     #[test]
     fn new_state() {
-        let state = State::new();
+        let state = State::from_config(Config::default());
 
         assert_eq!(state.config.total_at_stake_initial, 500000000000000);
         assert_eq!(state.validators.len(), 15625);
         assert_eq!(state.get_total_active_balance(), 500000000000000);
         assert_eq!(state.get_max_balance(), 32000000000);
         assert_eq!(state.get_min_balance(), 32000000000);
+        assert!(state.validators.iter().all(|v| v.is_active));
+    }
+
+    #[test]
+    fn new_state_queued_validators() {
+        let config = config::Config {
+            total_at_stake_queued: 320_000_000_000,
+            ..config::Config::default()
+        };
+        let state = State::from_config(config);
+
+        assert_eq!(state.validators.len(), 15635);
+        assert_eq!(state.get_total_active_validators(), 15625);
+        assert_eq!(
+            state.validators[15625].activation_epoch,
+            config::FAR_FUTURE_EPOCH
+        );
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let get_state = |seed| {
+            State::from_config(config::Config {
+                seed,
+                ..config::Config::default()
+            })
+        };
+        let mut state_a = get_state(42);
+        let mut state_b = get_state(42);
+        let mut state_c = get_state(43);
+
+        let proposers_a = state_a.pick_epoch_proposers();
+        assert_eq!(proposers_a, state_b.pick_epoch_proposers());
+        assert_ne!(proposers_a, state_c.pick_epoch_proposers());
+    }
+
+    #[test]
+    fn pick_sync_committee() {
+        let mut state = State::from_config(config::Config::default());
+        for validator in state.validators.iter_mut().skip(100) {
+            validator.is_active = false;
+        }
+
+        // only active validators, some of them more than once
+        let sync_committee = state.pick_sync_committee();
+        assert_eq!(512, sync_committee.len());
+        assert!(sync_committee.iter().all(|index| *index < 100));
+    }
+
+    #[test]
+    fn get_inactivity_scores() {
+        let mut state = State::from_config(config::Config::default());
+        state.validators[0].inactivity_score = 8;
+        state.validators[1].inactivity_score = 8;
+        state.validators[2].inactivity_score = 12;
+        state.validators[2].is_active = false;
+
+        // 15,625 genesis validators, the inactive one not counted
+        let inactivity_scores = state.get_inactivity_scores();
+        assert_eq!(Some(&15_622), inactivity_scores.get(&0));
+        assert_eq!(Some(&2), inactivity_scores.get(&8));
+        assert_eq!(None, inactivity_scores.get(&12));
+    }
+
+    #[test]
+    fn get_validator_churn_limit() {
+        let mut state = State::from_config(Config::default());
+        assert_eq!(state.get_validator_churn_limit(), 4);
+
+        for _ in 0..400_000 {
+            state
+                .validators
+                .push(get_dummy_validator(32, 32, true, false));
+        }
+        assert_eq!(state.get_validator_churn_limit(), 4);
+
+        // only once the epoch starts
+        let state_totals = StateTotals::new(&state);
+        state.update_validator_churn_limit(state_totals.active_validators);
+        assert_eq!(state.get_validator_churn_limit(), 6);
     }
 
     #[test]
     fn new_state_totals() {
-        let mut state = State::new();
+        let mut state = State::from_config(Config::default());
         state.validators = vec![];
         state
             .validators
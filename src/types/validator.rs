@@ -17,48 +17,128 @@ pub struct Validator {
     pub has_matched_source: bool,
     pub has_matched_target: bool,
     pub has_matched_head: bool,
+    // cast a slashable attestation in the previous epoch, not yet reported
+    pub has_equivocated: bool,
     pub is_proposer: bool,
+    pub activation_eligibility_epoch: u64,
+    pub activation_epoch: u64,
+    pub exit_epoch: u64,
+    pub withdrawable_epoch: u64,
+    // Altair, grows while the validator misses the target, and recovers
+    //   once it is back, faster outside of the inactivity leak
+    pub inactivity_score: u64,
 }
 
 impl Validator {
-    pub fn get_base_reward(&self, sqrt_total_active_balance: u64) -> u64 {
-        self.effective_balance * config::BASE_REWARD_FACTOR
+    // SPEC: get_validator_from_deposit
+    pub fn new(amount: u64, spec: &SpecConstants) -> Validator {
+        Validator {
+            balance: amount,
+            effective_balance: cmp::min(
+                amount - amount % spec.effective_balance_increment,
+                spec.max_effective_balance,
+            ),
+            is_active: false,
+            is_slashed: false,
+            has_matched_source: false,
+            has_matched_target: false,
+            has_matched_head: false,
+            has_equivocated: false,
+            is_proposer: false,
+            activation_eligibility_epoch: config::FAR_FUTURE_EPOCH,
+            activation_epoch: config::FAR_FUTURE_EPOCH,
+            exit_epoch: config::FAR_FUTURE_EPOCH,
+            withdrawable_epoch: config::FAR_FUTURE_EPOCH,
+            inactivity_score: 0,
+        }
+    }
+
+    pub fn is_active_validator(&self, epoch: u64) -> bool {
+        self.activation_epoch <= epoch && epoch < self.exit_epoch
+    }
+
+    pub fn is_slashable_validator(&self, epoch: u64) -> bool {
+        !self.is_slashed && self.activation_epoch <= epoch && epoch < self.withdrawable_epoch
+    }
+
+    // slashed validators are penalized until they can withdraw
+    pub fn is_eligible(&self, previous_epoch: u64) -> bool {
+        self.is_active || (self.is_slashed && previous_epoch + 1 < self.withdrawable_epoch)
+    }
+
+    pub fn is_eligible_for_activation_queue(&self, spec: &SpecConstants) -> bool {
+        self.activation_eligibility_epoch == config::FAR_FUTURE_EPOCH
+            && self.effective_balance == spec.max_effective_balance
+    }
+
+    pub fn is_eligible_for_activation(&self, finalized_epoch: u64) -> bool {
+        self.activation_eligibility_epoch <= finalized_epoch
+            && self.activation_epoch == config::FAR_FUTURE_EPOCH
+    }
+
+    pub fn get_base_reward(&self, sqrt_total_active_balance: u64, spec: &SpecConstants) -> u64 {
+        self.effective_balance * spec.base_reward_factor
             / sqrt_total_active_balance
-            / config::BASE_REWARDS_PER_EPOCH
+            / spec.base_rewards_per_epoch
     }
 
-    pub fn update_previous_epoch_activity(
+    // SPEC: get_base_reward of Altair, a reward per increment of effective balance
+    pub fn get_altair_base_reward(
         &self,
-        state: &State,
-        proposer_bitmap: &Vec<usize>,
+        sqrt_total_active_balance: u64,
+        spec: &SpecConstants,
+    ) -> u64 {
+        self.effective_balance / spec.effective_balance_increment
+            * spec.get_base_reward_per_increment(sqrt_total_active_balance)
+    }
+
+    pub fn update_previous_epoch_activity(
+        &mut self,
+        config: &Config,
+        rng: &mut StdRng,
+        proposer_bitmap: &[usize],
         validator_index: usize,
-    ) -> Validator {
-        let mut rng = thread_rng();
-        let has_been_online = state.config.probability_online > rng.gen();
-        let has_been_honest = state.config.probability_honest > rng.gen();
-        let has_matched_source = !self.is_slashed && has_been_online && has_been_honest;
+    ) {
+        let has_been_online = config.probability_online > rng.gen();
+        let has_been_honest = config.probability_honest > rng.gen();
+        let has_attested = !self.is_slashed && has_been_online;
+
+        // a dishonest validator misbehaves as set in the config
+        let behaviour = if has_been_honest {
+            None
+        } else {
+            Some(&config.dishonest_behaviour)
+        };
 
-        Validator {
-            balance: self.balance,
-            effective_balance: self.effective_balance,
-            is_active: self.is_active,
-            is_slashed: self.is_slashed,
-            has_matched_source: has_matched_source,
-            has_matched_target: has_matched_source,
-            has_matched_head: has_matched_source,
-            is_proposer: proposer_bitmap[validator_index] == 1,
-        }
+        // the matching sets are nested: a correct target requires a correct source,
+        //   and a correct head requires a correct target
+        let has_matched_source =
+            has_attested && behaviour != Some(&DishonestBehaviour::WrongSource);
+        let has_matched_target = has_matched_source
+            && behaviour != Some(&DishonestBehaviour::WrongTarget)
+            && config.probability_target > rng.gen();
+        let has_matched_head = has_matched_target
+            && behaviour != Some(&DishonestBehaviour::WrongHead)
+            && config.probability_head > rng.gen();
+        let has_equivocated =
+            self.is_active && has_attested && behaviour == Some(&DishonestBehaviour::Equivocation);
+
+        self.has_matched_source = has_matched_source;
+        self.has_matched_target = has_matched_target;
+        self.has_matched_head = has_matched_head;
+        self.has_equivocated = has_equivocated;
+        self.is_proposer = proposer_bitmap[validator_index] == 1;
     }
 
-    pub fn update_effective_balance(&mut self) {
-        let half_increment = config::EFFECTIVE_BALANCE_INCREMENT / 2;
+    pub fn update_effective_balance(&mut self, spec: &SpecConstants) {
+        let half_increment = spec.effective_balance_increment / 2;
 
         if self.balance < self.effective_balance
             || self.effective_balance + 3 * half_increment < self.balance
         {
             self.effective_balance = cmp::min(
-                self.balance - self.balance % config::EFFECTIVE_BALANCE_INCREMENT,
-                config::MAX_EFFECTIVE_BALANCE,
+                self.balance - self.balance % spec.effective_balance_increment,
+                spec.max_effective_balance,
             );
         }
     }
@@ -80,57 +160,135 @@ mod tests {
         probability_honest: f32,
         expected_result: bool,
     ) -> TestCaseHasSource {
-        let mut state = State::new();
+        let mut state = State::from_config(Config::default());
         let validator = Validator {
             balance: 32_000_000_000,
             effective_balance: 32_000_000_000,
             is_active: true,
-            is_slashed: is_slashed,
+            is_slashed,
             has_matched_source: false,
             has_matched_head: false,
+            has_equivocated: false,
             has_matched_target: false,
             is_proposer: false,
+            activation_eligibility_epoch: 0,
+            activation_epoch: 0,
+            exit_epoch: config::FAR_FUTURE_EPOCH,
+            withdrawable_epoch: config::FAR_FUTURE_EPOCH,
+            inactivity_score: 0,
         };
 
         state.config.probability_online = probability_online;
         state.config.probability_honest = probability_honest;
 
         TestCaseHasSource {
-            state: state,
-            validator: validator,
-            expected_result: expected_result,
+            state,
+            validator,
+            expected_result,
         }
     }
 
     #[test]
     fn update_previous_epoch_activity_has_matched_source() {
-        let mut cases = vec![];
-
-        // is_slashed true should always fail
-        cases.push(prepare_test_has_source(true, 1.0, 1.0, false));
-        cases.push(prepare_test_has_source(true, 1.0, 0.5, false));
-        cases.push(prepare_test_has_source(true, 1.0, 0.0, false));
-        cases.push(prepare_test_has_source(true, 0.0, 1.0, false));
-        cases.push(prepare_test_has_source(true, 0.0, 0.5, false));
-        cases.push(prepare_test_has_source(true, 0.0, 0.0, false));
-
-        // the "always good" case
-        cases.push(prepare_test_has_source(false, 1.0, 1.0, true));
-
-        // a 0.0 in one of the probabilities will always fail
-        cases.push(prepare_test_has_source(false, 0.0, 1.0, false));
-        cases.push(prepare_test_has_source(false, 1.0, 0.0, false));
+        let cases = vec![
+            // is_slashed true should always fail
+            prepare_test_has_source(true, 1.0, 1.0, false),
+            prepare_test_has_source(true, 1.0, 0.5, false),
+            prepare_test_has_source(true, 1.0, 0.0, false),
+            prepare_test_has_source(true, 0.0, 1.0, false),
+            prepare_test_has_source(true, 0.0, 0.5, false),
+            prepare_test_has_source(true, 0.0, 0.0, false),
+            // the "always good" case
+            prepare_test_has_source(false, 1.0, 1.0, true),
+            // a 0.0 in one of the probabilities will always fail
+            prepare_test_has_source(false, 0.0, 1.0, false),
+            prepare_test_has_source(false, 1.0, 0.0, false),
+        ];
 
         let dummy_vec = vec![cases[0].state.validators.len()];
 
         for mut case in cases {
-            case.validator =
-                case.validator
-                    .update_previous_epoch_activity(&case.state, &dummy_vec, 0);
+            case.validator.update_previous_epoch_activity(
+                &case.state.config,
+                &mut case.state.rng,
+                &dummy_vec,
+                0,
+            );
             assert_eq!(case.expected_result, case.validator.has_matched_source);
         }
     }
 
+    #[test]
+    fn update_previous_epoch_activity_nested_votes() {
+        // (probability_target, probability_head, expected source, target, head)
+        let cases = vec![
+            (1.0, 1.0, (true, true, true)),
+            (1.0, 0.0, (true, true, false)),
+            (0.0, 1.0, (true, false, false)),
+            (0.0, 0.0, (true, false, false)),
+        ];
+
+        for (probability_target, probability_head, expected_result) in cases {
+            let mut case = prepare_test_has_source(false, 1.0, 1.0, true);
+            case.state.config.probability_target = probability_target;
+            case.state.config.probability_head = probability_head;
+
+            let dummy_vec = vec![case.state.validators.len()];
+            case.validator.update_previous_epoch_activity(
+                &case.state.config,
+                &mut case.state.rng,
+                &dummy_vec,
+                0,
+            );
+
+            assert_eq!(
+                expected_result,
+                (
+                    case.validator.has_matched_source,
+                    case.validator.has_matched_target,
+                    case.validator.has_matched_head
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn update_previous_epoch_activity_dishonest_behaviour() {
+        // (behaviour, expected source, target, head, equivocation)
+        let cases = vec![
+            (
+                DishonestBehaviour::WrongSource,
+                (false, false, false, false),
+            ),
+            (DishonestBehaviour::WrongTarget, (true, false, false, false)),
+            (DishonestBehaviour::WrongHead, (true, true, false, false)),
+            (DishonestBehaviour::Equivocation, (true, true, true, true)),
+        ];
+
+        for (behaviour, expected_result) in cases {
+            let mut case = prepare_test_has_source(false, 1.0, 0.0, false);
+            case.state.config.dishonest_behaviour = behaviour;
+
+            let dummy_vec = vec![case.state.validators.len()];
+            case.validator.update_previous_epoch_activity(
+                &case.state.config,
+                &mut case.state.rng,
+                &dummy_vec,
+                0,
+            );
+
+            assert_eq!(
+                expected_result,
+                (
+                    case.validator.has_matched_source,
+                    case.validator.has_matched_target,
+                    case.validator.has_matched_head,
+                    case.validator.has_equivocated
+                )
+            );
+        }
+    }
+
     struct TestCaseProposer {
         state: State,
         validator: Validator,
@@ -140,7 +298,7 @@ mod tests {
     }
 
     fn prepare_test_proposer(validator_index: usize, expected_result: bool) -> TestCaseProposer {
-        let state = State::new();
+        let state = State::from_config(Config::default());
         let validator = Validator {
             balance: 32_000_000_000,
             effective_balance: 32_000_000_000,
@@ -148,24 +306,30 @@ mod tests {
             is_slashed: false,
             has_matched_source: false,
             has_matched_head: false,
+            has_equivocated: false,
             has_matched_target: false,
             is_proposer: false,
+            activation_eligibility_epoch: 0,
+            activation_epoch: 0,
+            exit_epoch: config::FAR_FUTURE_EPOCH,
+            withdrawable_epoch: config::FAR_FUTURE_EPOCH,
+            inactivity_score: 0,
         };
 
         let mut proposer_bitmap = vec![0; state.validators.len()];
-        proposer_bitmap[4 as usize] = 1;
-        proposer_bitmap[8 as usize] = 1;
-        proposer_bitmap[15 as usize] = 1;
-        proposer_bitmap[16 as usize] = 1;
-        proposer_bitmap[23 as usize] = 1;
-        proposer_bitmap[42 as usize] = 1;
+        proposer_bitmap[4_usize] = 1;
+        proposer_bitmap[8_usize] = 1;
+        proposer_bitmap[15_usize] = 1;
+        proposer_bitmap[16_usize] = 1;
+        proposer_bitmap[23_usize] = 1;
+        proposer_bitmap[42_usize] = 1;
 
         TestCaseProposer {
-            state: state,
-            validator: validator,
-            proposer_bitmap: proposer_bitmap,
-            validator_index: validator_index,
-            expected_result: expected_result,
+            state,
+            validator,
+            proposer_bitmap,
+            validator_index,
+            expected_result,
         }
     }
 
@@ -179,8 +343,9 @@ mod tests {
         cases.push(prepare_test_proposer(42, true));
 
         for mut case in cases {
-            case.validator = case.validator.update_previous_epoch_activity(
-                &case.state,
+            case.validator.update_previous_epoch_activity(
+                &case.state.config,
+                &mut case.state.rng,
                 &case.proposer_bitmap,
                 case.validator_index,
             );
@@ -198,14 +363,37 @@ mod tests {
             is_slashed: false,
             has_matched_source: false,
             has_matched_head: false,
+            has_equivocated: false,
             has_matched_target: false,
             is_proposer: false,
+            activation_eligibility_epoch: 0,
+            activation_epoch: 0,
+            exit_epoch: config::FAR_FUTURE_EPOCH,
+            withdrawable_epoch: config::FAR_FUTURE_EPOCH,
+            inactivity_score: 0,
         };
 
         // we pick sqrt of 500,000 ETH
         let sqrt_total_active_balance: u64 = 22_360_679;
 
-        assert_eq!(22_897, validator.get_base_reward(sqrt_total_active_balance));
+        let mut spec = SpecConstants::mainnet();
+        assert_eq!(
+            22_897,
+            validator.get_base_reward(sqrt_total_active_balance, &spec)
+        );
+
+        // the base reward is proportional to the BASE_REWARD_FACTOR
+        spec.set("BASE_REWARD_FACTOR", 128).unwrap();
+        assert_eq!(
+            45_794,
+            validator.get_base_reward(sqrt_total_active_balance, &spec)
+        );
+
+        // 2_862 Gwei per increment
+        assert_eq!(
+            91_584,
+            validator.get_altair_base_reward(sqrt_total_active_balance, &SpecConstants::mainnet())
+        );
     }
 
     struct TestCaseUpdateBalance {
@@ -214,7 +402,7 @@ mod tests {
     }
 
     fn eth_to_gwei(eth_number: f64) -> u64 {
-        (eth_number * 1_000_000_000 as f64) as u64
+        (eth_number * 1_000_000_000_f64) as u64
     }
 
     fn prepare_test_case_update_balance(
@@ -230,8 +418,14 @@ mod tests {
                 is_slashed: false,
                 has_matched_source: false,
                 has_matched_head: false,
+                has_equivocated: false,
                 has_matched_target: false,
                 is_proposer: false,
+                activation_eligibility_epoch: 0,
+                activation_epoch: 0,
+                exit_epoch: config::FAR_FUTURE_EPOCH,
+                withdrawable_epoch: config::FAR_FUTURE_EPOCH,
+                inactivity_score: 0,
             },
             expected_result: eth_to_gwei(expected_result),
         }
@@ -271,7 +465,8 @@ mod tests {
         cases.push(prepare_test_case_update_balance(32.500001, 31.0, 32.0));
 
         for mut case in cases {
-            case.validator.update_effective_balance();
+            case.validator
+                .update_effective_balance(&SpecConstants::mainnet());
             assert_eq!(case.expected_result, case.validator.effective_balance);
         }
     }